@@ -0,0 +1,229 @@
+// Bot arena: seats the given bots at one table, plays them against each other for a number of
+// hands and reports how each of them did. Games are freezeouts played one after the other until
+// the hands are dealt, all from seeded decks, so the same arguments replay the same match. Chip
+// conservation is checked after every hand.
+
+use poker::Evaluator;
+use rusty_poker::poker::bot::play_bots;
+use rusty_poker::poker::ledger::LedgerEntryKind;
+use rusty_poker::{Bot, BotKind, DeckSource, Game, GamePlayState, TableRecord};
+use std::collections::HashMap;
+use std::env;
+use std::process;
+use std::sync::Arc;
+use std::time::Instant;
+
+const USAGE: &str =
+    "usage: simulate [--hands N] [--seed S] [--stack CHIPS] [--big-blind CHIPS] BOT...
+  BOT is one of Random, CallingStation or TightAggressive, 3 to 10 of them, one per seat
+  defaults: 1000 hands, seed 0, stacks of 1000 chips, big blind of 10";
+
+struct Arena {
+    hands: u32,
+    seed: u64,
+    stack: u64,
+    big_blind: u64,
+    bots: Vec<BotKind>, // by seats
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Arena, String> {
+    let mut arena = Arena {
+        hands: 1000,
+        seed: 0,
+        stack: 1000,
+        big_blind: 10,
+        bots: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<u64, String> {
+            args.next()
+                .and_then(|value| value.parse().ok())
+                .ok_or(format!("{} takes a positive number", name))
+        };
+        match arg.as_str() {
+            "--hands" => {
+                arena.hands = u32::try_from(value("--hands")?)
+                    .map_err(|_| "--hands takes at most 4294967295 hands".to_string())?
+            }
+            "--seed" => arena.seed = value("--seed")?,
+            "--stack" => arena.stack = value("--stack")?,
+            "--big-blind" => arena.big_blind = value("--big-blind")?,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            bot => arena
+                .bots
+                .push(bot.parse().map_err(|err| format!("{}: {}", err, bot))?),
+        }
+    }
+    if arena.bots.len() < 3 || arena.bots.len() > 10 {
+        return Err(format!("a table takes 3 to 10 bots\n{}", USAGE));
+    }
+    if arena.hands == 0 || arena.big_blind < 2 || arena.stack < arena.big_blind {
+        return Err(format!(
+            "at least one hand and a stack covering the big blind\n{}",
+            USAGE
+        ));
+    }
+    Ok(arena)
+}
+
+// A fresh table for the bots, the n-th of the match deals from its own seeded deck
+fn new_table(arena: &Arena, game_index: u64) -> Result<Game, &'static str> {
+    let seats = arena.bots.len();
+    let table = TableRecord::new_table(seats, arena.big_blind / 2, arena.big_blind, arena.stack);
+    let mut game = Game::new_game(table)
+        .with_deck_source(DeckSource::seeded(arena.seed.wrapping_add(game_index)));
+    for (seat, kind) in arena.bots.iter().enumerate() {
+        game.join_game(seat as u8, &format!("{:?} {}", kind, seat + 1), 0)?;
+    }
+    for seat in 0..seats {
+        // the last one starts the game
+        game.set_ready(seat, true)?;
+    }
+    Ok(game)
+}
+
+// Plays the match, returns the number of games it took and the chips every seat won or lost in
+// each hand. A game ends once two players are left with chips, the next one starts over.
+fn play(arena: &Arena) -> Result<(u64, Vec<Vec<i64>>), String> {
+    let seats = arena.bots.len();
+    let evaluator = Arc::new(Evaluator::new());
+    let mut bots: HashMap<usize, Box<dyn Bot>> = arena
+        .bots
+        .iter()
+        .enumerate()
+        .map(|(seat, kind)| {
+            let seed = arena.seed.wrapping_add(seat as u64);
+            (seat, kind.new_bot(seed, evaluator.clone()))
+        })
+        .collect();
+    let mut results = vec![Vec::with_capacity(arena.hands as usize); seats];
+    let mut games = 0;
+    while results[0].len() < arena.hands as usize {
+        let mut game = new_table(arena, games)?;
+        games += 1;
+        while results[0].len() < arena.hands as usize {
+            let hand_number = game.hand_number();
+            play_bots(&mut game, &mut bots);
+            if game.hand_number() == hand_number && game.game_state != GamePlayState::Ended {
+                return Err(format!("hand {} of game {} got stuck", hand_number, games));
+            }
+            let net = hand_results(&game, hand_number, seats);
+            check_conservation(&game, hand_number, &net)?;
+            for (seat, chips) in net.into_iter().enumerate() {
+                results[seat].push(chips);
+            }
+            if game.game_state == GamePlayState::Ended {
+                break;
+            }
+        }
+    }
+    Ok((games, results))
+}
+
+// chips each seat won or lost in the hand, from its ledger entries
+fn hand_results(game: &Game, hand_number: u32, seats: usize) -> Vec<i64> {
+    let mut net = vec![0; seats];
+    for entry in game.ledger().entries_for_hand(hand_number) {
+        let chips = entry.amount as i64;
+        match entry.kind {
+            LedgerEntryKind::Win | LedgerEntryKind::Return => {
+                net[entry.seat_index as usize] += chips
+            }
+            LedgerEntryKind::Bet => net[entry.seat_index as usize] -= chips,
+            _ => (),
+        }
+    }
+    net
+}
+
+// A hand only moves chips between the players, the table holds what was bought in. The next
+// hand may have started already, its blinds are still on the table.
+fn check_conservation(game: &Game, hand_number: u32, net: &[i64]) -> Result<(), String> {
+    if net.iter().sum::<i64>() != 0 {
+        return Err(format!(
+            "hand {} won {} chips more than it lost",
+            hand_number,
+            net.iter().sum::<i64>()
+        ));
+    }
+    if let Some(alert) = game.ledger().alerts().first() {
+        return Err(format!(
+            "chip alert in hand {}, expected {} chips, found {}",
+            alert.hand_number, alert.expected, alert.actual
+        ));
+    }
+    let stacks: u64 = game
+        .players_by_seats
+        .iter()
+        .flatten()
+        .map(|player| player.balance + player.current_bet + player.total_bet)
+        .sum();
+    let expected = game.ledger().expected_table_chips();
+    match stacks == expected {
+        true => Ok(()),
+        false => Err(format!(
+            "after hand {} the table holds {} chips, {} were bought in",
+            hand_number, stacks, expected
+        )),
+    }
+}
+
+// mean in big blinds per 100 hands and the half width of its 95% confidence interval
+fn bb_per_100(results: &[i64], big_blind: u64) -> (f64, f64) {
+    let hands = results.len() as f64;
+    let bbs: Vec<f64> = results
+        .iter()
+        .map(|chips| *chips as f64 / big_blind as f64)
+        .collect();
+    let mean = bbs.iter().sum::<f64>() / hands;
+    let variance = match results.len() > 1 {
+        true => bbs.iter().map(|bb| (bb - mean).powi(2)).sum::<f64>() / (hands - 1.0),
+        false => 0.0,
+    };
+    (mean * 100.0, 1.96 * (variance / hands).sqrt() * 100.0)
+}
+
+fn main() {
+    let arena = match parse_args(env::args().skip(1)) {
+        Ok(arena) => arena,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    let started = Instant::now();
+    let (games, results) = match play(&arena) {
+        Ok(played) => played,
+        Err(err) => {
+            eprintln!("ALERT simulation failed: {}", err);
+            process::exit(1);
+        }
+    };
+    let elapsed = started.elapsed().as_secs_f64();
+
+    println!(
+        "{} hands in {} games with seed {}, starting stacks of {} big blinds, {:.1}s ({:.0} hands/s)",
+        arena.hands,
+        games,
+        arena.seed,
+        arena.stack / arena.big_blind,
+        elapsed,
+        arena.hands as f64 / elapsed.max(f64::EPSILON)
+    );
+    println!(
+        "{:<6}{:<18}{:>10}{:>12}{:>12}",
+        "seat", "bot", "bb/100", "95% ci", "net chips"
+    );
+    for (seat, kind) in arena.bots.iter().enumerate() {
+        let (mean, margin) = bb_per_100(&results[seat], arena.big_blind);
+        println!(
+            "{:<6}{:<18}{:>10.2}{:>12}{:>12}",
+            seat + 1,
+            format!("{:?}", kind),
+            mean,
+            format!("± {:.2}", margin),
+            results[seat].iter().sum::<i64>()
+        );
+    }
+}
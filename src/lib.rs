@@ -0,0 +1,20 @@
+// The poker engine: tables, players, cards, hand histories and the storage of games.
+// The HTTP server in main.rs is only one consumer of it, built with the "server" feature.
+pub mod poker;
+
+// the stable surface of the engine, everything else is reachable through poker::
+pub use poker::accounts::{Account, Accounts};
+pub use poker::bot::{Bot, BotKind};
+pub use poker::buy_in::{BuyInKind, BuyInModel};
+pub use poker::deck::{DeckProvider, DeckSource};
+pub use poker::equity::{equity, Equity, EquityResult, Holding};
+pub use poker::events::GameEvent;
+pub use poker::game::{parse_cards, Card, Color, Game, GamePhase, GamePlayState, Rank};
+pub use poker::games_manager::{GameData, GameState, GamesManager, HandSummary, PotResult};
+pub use poker::hints::TrainingHints;
+pub use poker::history::{HandAction, HandHistory, TableRecord};
+pub use poker::player::{Player, PlayerAction, PlayerData, PlayerState};
+pub use poker::range::{range_equity, Range};
+pub use poker::stats::{PlayerStats, StatsBook, StatsReport};
+pub use poker::storage::GameStore;
+pub use poker::table::{TableCommand, TableHandle};
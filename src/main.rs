@@ -1,20 +1,33 @@
-mod poker {
-    pub mod game;
-    pub mod games_manager;
-    pub mod player;
-}
-use poker::games_manager::GamesManager;
-use poker::games_manager::GamesManagerArc;
-use std::sync::{Arc, RwLock};
+use log::{info, warn};
+use poker::Evaluator;
+use rusty_poker::poker::accounts::{Account, AccountSeat, Accounts};
+use rusty_poker::poker::fairness::{verify_shuffle, ShuffleProof};
+use rusty_poker::poker::games_manager::GamesManagerArc;
+use rusty_poker::poker::pokerstars::export_hands;
+use rusty_poker::poker::reaper::ReaperConfig;
+use rusty_poker::poker::replay::replay_hand;
+use rusty_poker::{
+    equity, parse_cards, BotKind, BuyInKind, BuyInModel, Game, GameStore, GamesManager,
+    HandHistory, Holding, PlayerAction, Range, TableCommand, TableRecord,
+};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, fs};
 
 use actix_cors::Cors;
 use actix_session::{Session, SessionMiddleware};
 use actix_web::cookie::Key;
 use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
-use poker::player::PlayerAction;
 use serde::Deserialize;
 use uuid::Uuid;
 
+// how long /listen_changes waits for a change, in seconds
+const LISTEN_TIMEOUT: u64 = 30;
+// seconds between hands at tables where chips can be bought, unless the game asks otherwise
+const DEFAULT_HAND_PAUSE: u64 = 10;
+
 // handlers structures
 #[derive(Deserialize, Debug)]
 enum Action {
@@ -31,16 +44,48 @@ struct CreateGame {
     big_blind: u64,
     initial_balance: u64,
     bet_time: u64,
+    #[serde(default)]
+    buy_in: BuyInModel,
+    max_boards: Option<u8>,
+    #[serde(default)]
+    rabbit_hunt: bool,
+    #[serde(default)]
+    bots: Vec<BotKind>, // seated from the first seat on
+    #[serde(default)]
+    training: bool,
+    hand_pause: Option<u64>, // seconds between hands
+    #[serde(default)]
+    level_duration: u64, // seconds before the blinds double
+}
+
+#[derive(Deserialize)]
+struct AddBot {
+    game_id: Uuid,
+    seat: Option<u8>,
+    kind: BotKind,
 }
 
 #[derive(Deserialize)]
 struct JoinGame {
     game_id: Uuid,
-    player_name: String,
+    player_name: Option<String>, // guests only, accounts play under their username
     chosen_seat: u8,
     appearance_type: u8,
 }
 
+#[derive(Deserialize)]
+struct GameStateQuery {
+    game_id: Uuid,
+    #[serde(default)]
+    hud: bool, // stats of the other players at the table
+}
+
+#[derive(Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
 #[derive(Deserialize)]
 struct SetReady {
     game_id: Uuid,
@@ -52,6 +97,47 @@ struct GameId {
     game_id: Uuid,
 }
 
+#[derive(Deserialize)]
+struct LedgerQuery {
+    game_id: Uuid,
+    hand_number: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct EquityQuery {
+    hands: Vec<String>, // "AsKd", "random" or a list of hands
+    #[serde(default)]
+    board: String,
+    #[serde(default)]
+    dead: String,
+}
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    range: String,
+    #[serde(default)]
+    dead: String,
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    #[serde(default)]
+    step: usize,
+}
+
+#[derive(Deserialize)]
+struct BuyChips {
+    game_id: Uuid,
+    kind: BuyInKind,
+    amount: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ClientSeed {
+    game_id: Uuid,
+    seed: String,
+}
+
 #[derive(Deserialize)]
 struct PerformAction {
     game_id: Uuid,
@@ -71,6 +157,33 @@ fn check_joined(session: &Session) -> Result<(), HttpResponse> {
     }
 }
 
+// player id of a joined session, tables look up its seat
+fn viewer(session: &Session) -> Option<Uuid> {
+    check_joined(session).ok()?;
+    session.get::<Uuid>("player_id").ok()?
+}
+
+fn viewer_seat(viewer: Option<Uuid>, game: &Game) -> Option<usize> {
+    game.players.get(&viewer?).copied()
+}
+
+// username of a logged in session
+fn account(session: &Session) -> Option<String> {
+    session.get::<String>("account").ok()?
+}
+
+// player ids the session played the game with, an account's are kept for tables it left as well
+fn own_ids(viewer: Option<Uuid>, account: Option<Account>, game_id: Uuid) -> Vec<Uuid> {
+    let mut ids = account.map_or_else(Vec::new, |account| account.player_ids(game_id));
+    ids.extend(viewer);
+    ids
+}
+
+// nicknames are taken again by others, seats are found by the player ids
+fn own_seat(ids: &[Uuid], history: &HandHistory) -> Option<usize> {
+    ids.iter().find_map(|player_id| history.seat_of(*player_id))
+}
+
 // handlers
 #[get("/")]
 async fn hello() -> impl Responder {
@@ -82,26 +195,70 @@ async fn create_game(
     data: web::Data<GamesManagerArc>,
     body: web::Json<CreateGame>,
 ) -> impl Responder {
-    let mut games_manager = data.write().unwrap();
-    let new_game_id = games_manager.new_game(
-        body.seats_count,
-        body.small_blind,
-        body.big_blind,
-        body.initial_balance,
-    );
-
-    let response = serde_json::json!({
-        "message": "success",
-        "game_id": new_game_id
+    if data.shutting_down() {
+        return HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({"error": "server is shutting down"}));
+    }
+    if body.bots.len() >= body.seats_count {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "bots would take every seat"}));
+    }
+    let new_game_id = data.new_game(TableRecord {
+        max_players: body.seats_count,
+        small_blind: body.small_blind,
+        big_blind: body.big_blind,
+        initial_balance: body.initial_balance,
+        buy_in_model: body.buy_in,
+        max_boards: body.max_boards.unwrap_or(1),
+        rabbit_hunt: body.rabbit_hunt,
+        action_clock: body.bet_time,
+        training: body.training,
+        // busted players need the pause to buy back in before the next hand
+        hand_pause: body.hand_pause.unwrap_or(match body.buy_in {
+            BuyInModel::Freezeout => 0,
+            _ => DEFAULT_HAND_PAUSE,
+        }),
+        level_duration: body.level_duration,
     });
 
-    HttpResponse::Ok().json(response)
+    match new_game_id {
+        Err(err) => HttpResponse::BadRequest().json(serde_json::json!({"error": err})),
+        Ok(new_game_id) => {
+            let mut bot_seats = Vec::new();
+            for kind in &body.bots {
+                let seated = data
+                    .request(new_game_id, |reply| TableCommand::AddBot {
+                        seat_index: None,
+                        kind: *kind,
+                        reply,
+                    })
+                    .await;
+                match seated {
+                    Ok(Ok(seat)) => bot_seats.push(seat),
+                    // the game stays open with the bots seated so far
+                    Ok(Err(err)) | Err(err) => {
+                        return HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": err,
+                            "game_id": new_game_id,
+                            "bot_seats": bot_seats
+                        }))
+                    }
+                }
+            }
+            let response = serde_json::json!({
+                "message": "success",
+                "game_id": new_game_id,
+                "bot_seats": bot_seats
+            });
+
+            HttpResponse::Ok().json(response)
+        }
+    }
 }
 
 #[get("/games")]
 async fn games(data: web::Data<GamesManagerArc>) -> impl Responder {
-    let games_manager = data.read().unwrap();
-    let games = games_manager.get_all_games_data();
+    let games = data.get_all_games_data().await;
     let response = serde_json::json!({
         "message": "success",
         "games": games
@@ -113,6 +270,7 @@ async fn games(data: web::Data<GamesManagerArc>) -> impl Responder {
 #[post("/join_game")]
 async fn join_game(
     data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
     session: Session,
     body: web::Json<JoinGame>,
 ) -> impl Responder {
@@ -120,22 +278,75 @@ async fn join_game(
         return HttpResponse::Forbidden().json(serde_json::json!({"message": "error"}));
     }
 
-    let mut games_manager = data.write().unwrap();
+    if data.shutting_down() {
+        return HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({"message": "error", "content": "server is shutting down"}));
+    }
 
-    if let Ok(game) = games_manager.get_game_mut(body.game_id) {
-        match game.join_game(body.chosen_seat, &body.player_name, body.appearance_type) {
-            Err(err) => {
-                return HttpResponse::Forbidden()
-                    .json(serde_json::json!({"message": "error", "content": err}))
-            }
-            Ok(user_id) => {
-                session.insert("joined", true).unwrap();
-                session.insert("player_id", user_id).unwrap();
-                return HttpResponse::Ok().json(serde_json::json!({"message": "success"}));
+    // nicknames of accounts are theirs only
+    let account = account(&session);
+    let nickname = match (&account, &body.player_name) {
+        (Some(username), _) => username.clone(),
+        (None, Some(name)) if accounts.exists(name) => return HttpResponse::Forbidden().json(
+            serde_json::json!({"message": "error", "content": "nickname belongs to an account"}),
+        ),
+        (None, Some(name)) => name.clone(),
+        (None, None) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "guests need a player_name"}))
+        }
+    };
+
+    let joined = data
+        .request(body.game_id, |reply| TableCommand::Join {
+            seat_index: body.chosen_seat,
+            nickname,
+            appearance_type: body.appearance_type,
+            reply,
+        })
+        .await;
+    match joined {
+        Err(_) => HttpResponse::Forbidden().json(serde_json::json!({"message": "error"})),
+        Ok(Err(err)) => {
+            HttpResponse::Forbidden().json(serde_json::json!({"message": "error", "content": err}))
+        }
+        Ok(Ok(user_id)) => {
+            session.insert("joined", true).unwrap();
+            session.insert("player_id", user_id).unwrap();
+            if let Some(username) = account {
+                let seat = AccountSeat {
+                    game_id: body.game_id,
+                    player_id: user_id,
+                };
+                // the seat is taken anyway, only another device would not find it
+                let _ = accounts.set_seat(&username, Some(seat));
             }
+            HttpResponse::Ok().json(serde_json::json!({"message": "success"}))
+        }
+    }
+}
+
+#[post("/add_bot")]
+async fn add_bot(data: web::Data<GamesManagerArc>, body: web::Json<AddBot>) -> impl Responder {
+    if data.shutting_down() {
+        return HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({"message": "error", "content": "server is shutting down"}));
+    }
+
+    let added = data
+        .request(body.game_id, |reply| TableCommand::AddBot {
+            seat_index: body.seat,
+            kind: body.kind,
+            reply,
+        })
+        .await;
+    match added {
+        Err(err) | Ok(Err(err)) => {
+            HttpResponse::Forbidden().json(serde_json::json!({"message": "error", "content": err}))
+        }
+        Ok(Ok(seat)) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "seat": seat}))
         }
-    } else {
-        HttpResponse::Forbidden().json(serde_json::json!({"message": "error"}))
     }
 }
 
@@ -149,23 +360,19 @@ async fn set_ready(
         return err;
     }
 
-    let mut response = serde_json::json!({
+    let player_id = session.get::<Uuid>("player_id").unwrap().unwrap();
+    println!("ddd {} {}", player_id, body.new_ready_state);
+    let _ = data
+        .request(body.game_id, |reply| TableCommand::SetReady {
+            player_id,
+            ready: body.new_ready_state,
+            reply,
+        })
+        .await;
+    let response = serde_json::json!({
         "message": "success"
     });
 
-    let mut games_manager = data.write().unwrap();
-    if let Ok(game) = games_manager.get_game_mut(body.game_id) {
-        let player_index = game
-            .players
-            .get(&session.get::<Uuid>("player_id").unwrap().unwrap())
-            .unwrap();
-        println!("ddd {} {}", *player_index, body.new_ready_state);
-        let ok = game.set_ready(*player_index, body.new_ready_state);
-        response = serde_json::json!({
-            "message": "success",
-        });
-    }
-
     return HttpResponse::Ok().json(response);
 }
 
@@ -173,54 +380,94 @@ async fn set_ready(
 async fn game_state(
     data: web::Data<GamesManagerArc>,
     session: Session,
-    query: web::Query<GameId>,
+    query: web::Query<GameStateQuery>,
 ) -> impl Responder {
-    let games_manager = data.write().unwrap();
-
     if let Err(_err) = check_joined(&session) {
-        let game_state = games_manager.get_game_state(query.game_id, Uuid::new_v4());
+        let game_state = data.get_game_state(query.game_id, Uuid::new_v4()).await;
 
         match game_state {
             Err(err) => {
                 return HttpResponse::Forbidden()
                     .json(serde_json::json!({"message": "error", "content": err }))
             }
-            Ok(game) => {
+            Ok(mut game) => {
+                if query.hud {
+                    data.add_hud(&mut game);
+                }
                 return HttpResponse::Ok()
-                    .json(serde_json::json!({"message": "success", "game_state": game}))
+                    .json(serde_json::json!({"message": "success", "game_state": game}));
             }
         }
     }
-    let game_state = games_manager.get_game_state(
-        query.game_id,
-        session.get::<Uuid>("player_id").unwrap().unwrap(),
-    );
+    let game_state = data
+        .get_game_state(
+            query.game_id,
+            session.get::<Uuid>("player_id").unwrap().unwrap(),
+        )
+        .await;
 
     match game_state {
         Err(err) => {
             return HttpResponse::Forbidden()
                 .json(serde_json::json!({"message": "error", "content": err }))
         }
-        Ok(game) => {
+        Ok(mut game) => {
+            if query.hud {
+                data.add_hud(&mut game);
+            }
             return HttpResponse::Ok()
-                .json(serde_json::json!({"message": "success", "game_state": game}))
+                .json(serde_json::json!({"message": "success", "game_state": game}));
         }
     }
 }
 
 #[get("/listen_changes")]
-async fn listen_changes(session: Session, query: web::Query<GameId>) -> impl Responder {
-    // long polling dont work
+async fn listen_changes(
+    data: web::Data<GamesManagerArc>,
+    session: Session,
+    query: web::Query<GameStateQuery>,
+) -> impl Responder {
     if let Err(err) = check_joined(&session) {
         return err;
     }
+    let table = match data.table(query.game_id) {
+        Err(err) => {
+            return HttpResponse::Forbidden()
+                .json(serde_json::json!({"message": "error", "content": err }))
+        }
+        Ok(table) => table,
+    };
 
-    let response = serde_json::json!({
-        "message": "updated",
-        "game_state": query.game_id
-    });
+    // waits for the next change of the table, or answers that nothing changed
+    let mut updates = table.updates();
+    updates.borrow_and_update();
+    let timeout = Duration::from_secs(LISTEN_TIMEOUT);
+    match actix_web::rt::time::timeout(timeout, updates.changed()).await {
+        Err(_) => {
+            return HttpResponse::Ok().json(serde_json::json!({"message": "nothing changed"}))
+        }
+        Ok(Err(_)) => {
+            return HttpResponse::Forbidden()
+                .json(serde_json::json!({"message": "error", "content": "game not found"}))
+        }
+        Ok(Ok(_)) => (),
+    }
 
-    HttpResponse::Ok().json(response)
+    let player_id = session.get::<Uuid>("player_id").unwrap().unwrap();
+    match table
+        .inspect(move |game| game.collect_state_data(player_id))
+        .await
+    {
+        Err(err) => {
+            HttpResponse::Forbidden().json(serde_json::json!({"message": "error", "content": err }))
+        }
+        Ok(mut state) => {
+            if query.hud {
+                data.add_hud(&mut state);
+            }
+            HttpResponse::Ok().json(serde_json::json!({"message": "updated", "game_state": state}))
+        }
+    }
 }
 
 #[post("/perform_action")]
@@ -232,14 +479,15 @@ async fn perform_action(
     if let Err(err) = check_joined(&session) {
         return err;
     }
-    let mut games_manager = data.write().unwrap();
-    if let Ok(game) = games_manager.get_game_mut(body.game_id) {
-        let player_index = game
-            .players
-            .get(&session.get::<Uuid>("player_id").unwrap().unwrap())
-            .unwrap();
-        game.player_action(*player_index, body.action, body.bet.unwrap());
-    } else {
+    let performed = data
+        .request(body.game_id, |reply| TableCommand::Act {
+            player_id: session.get::<Uuid>("player_id").unwrap().unwrap(),
+            action: body.action,
+            amount: body.bet.unwrap(),
+            reply,
+        })
+        .await;
+    if let Err(_) | Ok(Err(_)) = performed {
         return HttpResponse::Forbidden().json(serde_json::json!({"message": "error"}));
     }
 
@@ -250,30 +498,574 @@ async fn perform_action(
     HttpResponse::Ok().json(response)
 }
 
+#[post("/buy_chips")]
+async fn buy_chips(
+    data: web::Data<GamesManagerArc>,
+    session: Session,
+    body: web::Json<BuyChips>,
+) -> impl Responder {
+    if let Err(err) = check_joined(&session) {
+        return err;
+    }
+    let bought = data
+        .request(body.game_id, |reply| TableCommand::BuyChips {
+            player_id: session.get::<Uuid>("player_id").unwrap().unwrap(),
+            kind: body.kind,
+            amount: body.amount.unwrap_or(0),
+            reply,
+        })
+        .await;
+    match bought {
+        Err(_) => HttpResponse::Forbidden().json(serde_json::json!({"message": "error"})),
+        Ok(Err(err)) => {
+            HttpResponse::Forbidden().json(serde_json::json!({"message": "error", "content": err}))
+        }
+        Ok(Ok(chips)) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "chips": chips}))
+        }
+    }
+}
+
+#[get("/ledger")]
+async fn ledger(
+    data: web::Data<GamesManagerArc>,
+    query: web::Query<LedgerQuery>,
+) -> impl Responder {
+    let hand_number = query.hand_number;
+    let ledger = data
+        .inspect(query.game_id, move |game| {
+            let entries = match hand_number {
+                Some(hand_number) => game.ledger().entries_for_hand(hand_number),
+                None => game.ledger().entries().to_vec(),
+            };
+            (entries, game.ledger().alerts().to_vec())
+        })
+        .await;
+    match ledger {
+        Err(err) => {
+            HttpResponse::Forbidden().json(serde_json::json!({"message": "error", "content": err }))
+        }
+        Ok((entries, alerts)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "success",
+            "ledger": entries,
+            "alerts": alerts
+        })),
+    }
+}
+
+#[post("/client_seed")]
+async fn client_seed(
+    data: web::Data<GamesManagerArc>,
+    session: Session,
+    body: web::Json<ClientSeed>,
+) -> impl Responder {
+    if let Err(err) = check_joined(&session) {
+        return err;
+    }
+    let seeded = data
+        .request(body.game_id, |reply| TableCommand::SetClientSeed {
+            player_id: session.get::<Uuid>("player_id").unwrap().unwrap(),
+            seed: body.seed.clone(),
+            reply,
+        })
+        .await;
+    match seeded {
+        Err(_) => HttpResponse::Forbidden().json(serde_json::json!({"message": "error"})),
+        Ok(Err(err)) => {
+            HttpResponse::Forbidden().json(serde_json::json!({"message": "error", "content": err}))
+        }
+        Ok(Ok(_)) => HttpResponse::Ok().json(serde_json::json!({"message": "success"})),
+    }
+}
+
+#[post("/verify_shuffle")]
+async fn verify_shuffle_proof(body: web::Json<ShuffleProof>) -> impl Responder {
+    match verify_shuffle(&body) {
+        Err(err) => HttpResponse::BadRequest().json(serde_json::json!({"error": err})),
+        Ok(deck) => HttpResponse::Ok()
+            .json(serde_json::json!({"message": "success", "deck": deck.to_vec()})),
+    }
+}
+
+#[post("/tools/equity")]
+async fn equity_tool(
+    evaluator: web::Data<Evaluator>,
+    body: web::Json<EquityQuery>,
+) -> impl Responder {
+    let holdings: Result<Vec<Holding>, &str> = body.hands.iter().map(|hand| hand.parse()).collect();
+    let (holdings, board, dead) =
+        match (holdings, parse_cards(&body.board), parse_cards(&body.dead)) {
+            (Ok(holdings), Ok(board), Ok(dead)) => (holdings, board, dead),
+            (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({"error": err}))
+            }
+        };
+    // enumerating runouts takes a while, it runs off the async workers
+    let result = web::block(move || {
+        equity(
+            &evaluator,
+            &holdings,
+            &board,
+            &dead,
+            &mut rand::thread_rng(),
+        )
+    })
+    .await;
+    match result {
+        Err(_) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": "equity calculation failed"})),
+        Ok(Err(err)) => HttpResponse::BadRequest().json(serde_json::json!({"error": err})),
+        Ok(Ok(equity)) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "equity": equity}))
+        }
+    }
+}
+
+#[post("/tools/range")]
+async fn range_tool(body: web::Json<RangeQuery>) -> impl Responder {
+    match (body.range.parse::<Range>(), parse_cards(&body.dead)) {
+        (Ok(range), Ok(dead)) => {
+            let range = range.without(&dead);
+            HttpResponse::Ok().json(serde_json::json!({
+                "message": "success",
+                "combos": range.len(),
+                "hands": range.notation()
+            }))
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            HttpResponse::BadRequest().json(serde_json::json!({"error": err}))
+        }
+    }
+}
+
+#[get("/games/{game_id}/hands")]
+async fn hand_histories(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let game_id = path.into_inner();
+    let account = account(&session).and_then(|username| accounts.account(&username));
+    let ids = own_ids(viewer(&session), account, game_id);
+    let hands = data
+        .inspect(game_id, move |game| {
+            game.hand_histories()
+                .iter()
+                .map(|history| history.visible_to(own_seat(&ids, history)))
+                .collect::<Vec<_>>()
+        })
+        .await;
+    match hands {
+        Err(err) => game_not_found(err),
+        Ok(hands) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "hands": hands}))
+        }
+    }
+}
+
+#[get("/games/{game_id}/hands/pokerstars")]
+async fn pokerstars_hands(
+    data: web::Data<GamesManagerArc>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let game_id = path.into_inner();
+    let text = data
+        .inspect(game_id, move |game| {
+            let hands: Vec<_> = game
+                .hand_histories()
+                .iter()
+                .map(|history| history.visible_to(None))
+                .collect();
+            export_hands(game_id, &hands, None)
+        })
+        .await;
+    match text {
+        Err(err) => game_not_found(err),
+        Ok(text) => pokerstars_response(game_id, text),
+    }
+}
+
+#[get("/games/{game_id}/hands/pokerstars/me")]
+async fn pokerstars_own_hands(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let game_id = path.into_inner();
+    let viewer = viewer(&session);
+    let account = account(&session).and_then(|username| accounts.account(&username));
+    let logged_in = account.is_some();
+    let ids = own_ids(viewer, account, game_id);
+    let text = data
+        .inspect(game_id, move |game| {
+            if !logged_in && viewer_seat(viewer, game).is_none() {
+                return None;
+            }
+            // only the hands the player was dealt into
+            let mut text = String::new();
+            for history in game.hand_histories() {
+                let own = match own_seat(&ids, history) {
+                    Some(own) => own,
+                    None => continue,
+                };
+                let dealt_in = history
+                    .seats
+                    .iter()
+                    .any(|record| record.seat_index as usize == own && record.cards.is_some());
+                if dealt_in {
+                    let hand = history.visible_to(Some(own));
+                    text.push_str(&export_hands(game_id, &[hand], Some(own)));
+                }
+            }
+            Some(text)
+        })
+        .await;
+    match text {
+        Err(err) => game_not_found(err),
+        Ok(None) => HttpResponse::Unauthorized().body("Unauthorized"),
+        Ok(Some(text)) => pokerstars_response(game_id, text),
+    }
+}
+
+fn game_not_found(err: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({"message": "error", "content": err }))
+}
+
+fn pokerstars_response(game_id: Uuid, text: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.txt\"", game_id),
+        ))
+        .body(text)
+}
+
+#[get("/games/{game_id}/hands/{hand_number}")]
+async fn hand_history(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    path: web::Path<(Uuid, u32)>,
+) -> impl Responder {
+    let (game_id, hand_number) = path.into_inner();
+    let account = account(&session).and_then(|username| accounts.account(&username));
+    let ids = own_ids(viewer(&session), account, game_id);
+    let hand = data
+        .inspect(game_id, move |game| {
+            game.hand_history(hand_number)
+                .map(|history| history.visible_to(own_seat(&ids, history)))
+        })
+        .await;
+    match hand {
+        Err(err) => game_not_found(err),
+        Ok(None) => HttpResponse::NotFound()
+            .json(serde_json::json!({"message": "error", "content": "hand not found"})),
+        Ok(Some(hand)) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "hand": hand}))
+        }
+    }
+}
+
+#[get("/games/{game_id}/hands/{hand_number}/replay")]
+async fn replay(
+    data: web::Data<GamesManagerArc>,
+    evaluator: web::Data<Evaluator>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    path: web::Path<(Uuid, u32)>,
+    query: web::Query<ReplayQuery>,
+) -> impl Responder {
+    let (game_id, hand_number) = path.into_inner();
+    let account = account(&session).and_then(|username| accounts.account(&username));
+    let ids = own_ids(viewer(&session), account, game_id);
+    // the hand is copied out, replaying it does not hold up the table
+    let found = data
+        .inspect(game_id, move |game| {
+            game.hand_history(hand_number).map(|history| {
+                let seat = own_seat(&ids, history);
+                (history.clone(), seat)
+            })
+        })
+        .await;
+    let (history, seat) = match found {
+        Err(err) => return game_not_found(err),
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"message": "error", "content": "hand not found"}))
+        }
+        Ok(Some(found)) => found,
+    };
+    let step = query.step;
+    let evaluator = evaluator.into_inner();
+    // the engine plays the hand again, that does not hold up a worker
+    let replayed = web::block(move || replay_hand(&history, step, seat, evaluator)).await;
+    match replayed {
+        Err(_) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": "could not replay the hand"})),
+        Ok(Err(err)) => HttpResponse::BadRequest().json(serde_json::json!({"error": err})),
+        Ok(Ok(replay)) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "replay": replay}))
+        }
+    }
+}
+
+#[get("/players/{nickname}/stats")]
+async fn player_stats(data: web::Data<GamesManagerArc>, path: web::Path<String>) -> impl Responder {
+    match data.player_stats(&path.into_inner()) {
+        None => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "no hands recorded for this player"})),
+        Some(stats) => {
+            HttpResponse::Ok().json(serde_json::json!({"message": "success", "stats": stats}))
+        }
+    }
+}
+
+#[post("/register")]
+async fn register(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    body: web::Json<Credentials>,
+) -> impl Responder {
+    if account(&session).is_some() || check_joined(&session).is_ok() {
+        return HttpResponse::Forbidden().json(
+            serde_json::json!({"message": "error", "content": "log out or leave the table first"}),
+        );
+    }
+    // the account would inherit the hands and stats of the guest playing as it
+    if data.nickname_in_use(&body.username).await {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "username taken"}));
+    }
+    let username = body.username.clone();
+    // hashing is slow on purpose, it does not hold up a worker
+    let registered = web::block(move || accounts.register(&body.username, &body.password)).await;
+    match registered {
+        Err(_) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": "could not register"})),
+        Ok(Err(err)) => HttpResponse::BadRequest().json(serde_json::json!({"error": err})),
+        Ok(Ok(())) => {
+            session.insert("account", username).unwrap();
+            HttpResponse::Created().json(serde_json::json!({"message": "success"}))
+        }
+    }
+}
+
+// Takes the account's seat back, the same from any device
+#[post("/login")]
+async fn login(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    body: web::Json<Credentials>,
+) -> impl Responder {
+    if account(&session).is_some() || check_joined(&session).is_ok() {
+        return HttpResponse::Forbidden().json(
+            serde_json::json!({"message": "error", "content": "log out or leave the table first"}),
+        );
+    }
+    let checked = accounts.clone();
+    let logged_in = web::block(move || checked.login(&body.username, &body.password)).await;
+    let account = match logged_in {
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "could not log in"}))
+        }
+        Ok(Err(err)) => {
+            return HttpResponse::Unauthorized()
+                .json(serde_json::json!({"message": "error", "content": err}))
+        }
+        Ok(Ok(account)) => account,
+    };
+
+    let mut game_id = None;
+    if let Some(seat) = account.seat {
+        let player_id = seat.player_id;
+        let seated = data
+            .inspect(seat.game_id, move |game| {
+                game.players.contains_key(&player_id)
+            })
+            .await;
+        match seated {
+            Ok(true) => {
+                session.insert("joined", true).unwrap();
+                session.insert("player_id", player_id).unwrap();
+                game_id = Some(seat.game_id);
+            }
+            // the table is gone or the seat was given up
+            _ => {
+                let _ = accounts.set_seat(&account.username, None);
+            }
+        }
+    }
+    session.insert("account", account.username).unwrap();
+    HttpResponse::Ok().json(serde_json::json!({"message": "success", "game_id": game_id}))
+}
+
+// The seat stays taken, logging in again takes it back
+#[post("/logout")]
+async fn logout(session: Session) -> impl Responder {
+    if account(&session).is_none() {
+        return HttpResponse::Unauthorized().body("Unauthorized");
+    }
+    session.purge();
+    HttpResponse::Ok().json(serde_json::json!({"message": "success"}))
+}
+
+#[get("/account")]
+async fn account_data(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+) -> impl Responder {
+    let account = match account(&session).and_then(|username| accounts.account(&username)) {
+        None => return HttpResponse::Unauthorized().body("Unauthorized"),
+        Some(account) => account,
+    };
+    let stats = data.player_stats(&account.username);
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "success",
+        "account": {
+            "username": account.username,
+            "created_at": account.created_at,
+            "bankroll": stats.as_ref().map_or(0, |stats| stats.net_chips),
+            "stats": stats,
+            "game_id": account.seat.map(|seat| seat.game_id),
+            "games": account.games
+        }
+    }))
+}
+
 #[post("/quit_game")]
-async fn quit_game(session: Session) -> impl Responder {
+async fn quit_game(
+    data: web::Data<GamesManagerArc>,
+    accounts: web::Data<Accounts>,
+    session: Session,
+    body: web::Json<GameId>,
+) -> impl Responder {
     if let Err(err) = check_joined(&session) {
         return err;
     }
 
+    // chips can only be taken off the table while no game is running
+    let cashed_out = data
+        .request(body.game_id, |reply| TableCommand::Quit {
+            player_id: session.get::<Uuid>("player_id").unwrap().unwrap(),
+            reply,
+        })
+        .await
+        .ok()
+        .and_then(|cashed_out| cashed_out.ok());
+
     session.remove("joined");
+    // a seat that could not be given up is found again by logging in
+    if let (Some(username), Some(_)) = (account(&session), cashed_out) {
+        let _ = accounts.set_seat(&username, None);
+    }
 
     let response = serde_json::json!({
-        "message": "success"
+        "message": "success",
+        "cashed_out": cashed_out
     });
 
     HttpResponse::Ok().json(response)
 }
 
+// Waits for the hands in progress to finish once every game is paused. Hands still running after
+// the grace period are frozen, their events are in storage and they go on after the restart.
+async fn drain_games(games_manager: GamesManagerArc, grace: Duration) {
+    games_manager.begin_shutdown().await;
+    info!(
+        "shutting down, waiting up to {}s for hands in progress",
+        grace.as_secs()
+    );
+    let deadline = Instant::now() + grace;
+    loop {
+        let in_progress = games_manager.hands_in_progress().await;
+        if in_progress == 0 {
+            info!("all hands finished");
+            return;
+        }
+        if Instant::now() >= deadline {
+            info!(
+                "{} hands still in progress, resuming them after restart",
+                in_progress
+            );
+            return;
+        }
+        actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+// archives abandoned and finished games every few seconds, they leave the lobby with it
+async fn run_reaper(games_manager: GamesManagerArc, config: ReaperConfig) {
+    info!("reaping games with {:?}", config);
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(config.interval));
+    loop {
+        interval.tick().await;
+        games_manager.reap(&config).await;
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+// cookie key kept on disk, so sessions stay valid across restarts
+fn session_key(path: &Path) -> io::Result<Key> {
+    match fs::read(path) {
+        Ok(bytes) if bytes.len() >= 64 => return Ok(Key::from(&bytes)),
+        Ok(_) => warn!(
+            "session key in {} too short, generating a new one",
+            path.display()
+        ),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err),
+    }
+    let key = Key::generate();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(key.master())?;
+    Ok(key)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let secret_key = Key::generate();
+    // RUST_LOG=debug shows every action the engine takes
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let data_dir = env::var("RUSTY_POKER_DATA").unwrap_or("data".to_string());
+    let store = GameStore::open(Path::new(&data_dir).join("games"))?;
+    let accounts = web::Data::new(Accounts::with_store(store.clone())?);
+    let secret_key = session_key(&Path::new(&data_dir).join("session.key"))?;
 
-    let games_manager = Arc::new(RwLock::new(GamesManager::new_manager()));
+    let games_manager = Arc::new(GamesManager::with_store(store)?);
+    let shutdown_grace = env::var("RUSTY_POKER_SHUTDOWN_GRACE")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(120);
 
-    HttpServer::new(move || {
+    let server_games_manager = games_manager.clone();
+    // building an evaluator takes a while, every worker shares this one
+    let evaluator = web::Data::new(Evaluator::new());
+    let server = HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(games_manager.clone()))
+            .app_data(web::Data::new(server_games_manager.clone()))
+            .app_data(evaluator.clone())
+            .app_data(accounts.clone())
             .wrap(SessionMiddleware::new(
                 actix_session::storage::CookieSessionStore::default(),
                 secret_key.clone(),
@@ -283,13 +1075,47 @@ async fn main() -> std::io::Result<()> {
             .service(create_game)
             .service(games)
             .service(join_game)
+            .service(add_bot)
             .service(set_ready)
             .service(game_state)
             .service(listen_changes)
             .service(perform_action)
+            .service(buy_chips)
+            .service(ledger)
+            .service(client_seed)
+            .service(verify_shuffle_proof)
+            .service(register)
+            .service(login)
+            .service(logout)
+            .service(account_data)
             .service(quit_game)
+            .service(
+                web::scope("/v1")
+                    .service(equity_tool)
+                    .service(range_tool)
+                    .service(hand_histories)
+                    // registered before hand_history, whose path would match them as well
+                    .service(pokerstars_hands)
+                    .service(pokerstars_own_hands)
+                    .service(hand_history)
+                    .service(replay)
+                    .service(player_stats),
+            )
     })
+    // signals are handled below, hands get to finish before the server stops
+    .disable_signals()
     .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    .run();
+
+    actix_web::rt::spawn(run_reaper(games_manager.clone(), ReaperConfig::from_env()));
+
+    let server_handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        drain_games(games_manager.clone(), Duration::from_secs(shutdown_grace)).await;
+        let _ = web::block(move || games_manager.flush_stats()).await;
+        server_handle.stop(true).await;
+    });
+
+    server.await
 }
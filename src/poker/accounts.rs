@@ -0,0 +1,201 @@
+use crate::poker::reaper::unix_now;
+use crate::poker::storage::GameStore;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+pub const MIN_USERNAME_LENGTH: usize = 3;
+pub const MAX_USERNAME_LENGTH: usize = 20;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+// Seat an account holds at a running table, taken back by logging in from another device
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct AccountSeat {
+    pub game_id: Uuid,
+    pub player_id: Uuid,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Account {
+    pub username: String,      // also the nickname at every table
+    pub password_hash: String, // argon2, with its salt and parameters
+    pub created_at: u64,       // unix timestamp in seconds
+    pub seat: Option<AccountSeat>,
+    pub games: Vec<Uuid>, // every game joined, in order
+    #[serde(default)]
+    pub seats: Vec<AccountSeat>, // every seat taken, the account's hands are found by them
+}
+
+impl Account {
+    // ids the account played with at the game, one for every time it joined
+    pub fn player_ids(&self, game_id: Uuid) -> Vec<Uuid> {
+        self.seats
+            .iter()
+            .filter(|seat| seat.game_id == game_id)
+            .map(|seat| seat.player_id)
+            .collect()
+    }
+}
+
+// Registered players, usernames are kept as typed but found whatever their case. Kept in the
+// store as a whole, hashing is slow on purpose so it runs outside of the lock.
+pub struct Accounts {
+    accounts: RwLock<HashMap<String, Account>>, // by lowercase username
+    store: Option<GameStore>,
+}
+
+// "Alice" is the account of "alice" as well
+fn key(username: &str) -> String {
+    username.to_ascii_lowercase()
+}
+
+fn validate(username: &str, password: &str) -> Result<(), &'static str> {
+    if username.len() < MIN_USERNAME_LENGTH || username.len() > MAX_USERNAME_LENGTH {
+        return Err("a username takes 3 to 20 characters");
+    }
+    // bots' nicknames have spaces, no account can take one
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("a username only takes letters, digits, _ and -");
+    }
+    if password.len() < MIN_PASSWORD_LENGTH {
+        return Err("a password takes at least 8 characters");
+    }
+    Ok(())
+}
+
+impl Accounts {
+    pub fn new_accounts() -> Accounts {
+        Accounts {
+            accounts: RwLock::new(HashMap::new()),
+            store: None,
+        }
+    }
+
+    pub fn with_store(store: GameStore) -> io::Result<Accounts> {
+        let accounts: HashMap<String, Account> =
+            store.load_document("accounts")?.unwrap_or_default();
+        // stores written before the keys were lowercase
+        let accounts = accounts
+            .into_iter()
+            .map(|(username, account)| (key(&username), account))
+            .collect();
+        Ok(Accounts {
+            accounts: RwLock::new(accounts),
+            store: Some(store),
+        })
+    }
+
+    pub fn register(&self, username: &str, password: &str) -> Result<(), &'static str> {
+        validate(username, password)?;
+        if self.exists(username) {
+            return Err("username taken");
+        }
+        let salt = SaltString::generate(&mut rand::rngs::OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|_| "could not hash the password")?
+            .to_string();
+        let mut accounts = self.accounts.write().unwrap();
+        // somebody else may have registered it while hashing
+        if accounts.contains_key(&key(username)) {
+            return Err("username taken");
+        }
+        accounts.insert(
+            key(username),
+            Account {
+                username: username.to_string(),
+                password_hash,
+                created_at: unix_now(),
+                seat: None,
+                games: Vec::new(),
+                seats: Vec::new(),
+            },
+        );
+        let saved = self.save(&accounts);
+        if saved.is_err() {
+            accounts.remove(&key(username));
+        }
+        saved
+    }
+
+    // the account when the password matches, the same error otherwise to not tell which was wrong
+    pub fn login(&self, username: &str, password: &str) -> Result<Account, &'static str> {
+        let account = self.account(username).ok_or("wrong username or password")?;
+        let hash =
+            PasswordHash::new(&account.password_hash).map_err(|_| "wrong username or password")?;
+        match Argon2::default().verify_password(password.as_bytes(), &hash) {
+            Ok(()) => Ok(account),
+            Err(_) => Err("wrong username or password"),
+        }
+    }
+
+    pub fn exists(&self, username: &str) -> bool {
+        self.accounts.read().unwrap().contains_key(&key(username))
+    }
+
+    pub fn account(&self, username: &str) -> Option<Account> {
+        self.accounts.read().unwrap().get(&key(username)).cloned()
+    }
+
+    // None once the account left its table
+    pub fn set_seat(&self, username: &str, seat: Option<AccountSeat>) -> Result<(), &'static str> {
+        let mut accounts = self.accounts.write().unwrap();
+        let account = accounts
+            .get_mut(&key(username))
+            .ok_or("account not found")?;
+        account.seat = seat;
+        if let Some(seat) = seat {
+            if !account.games.contains(&seat.game_id) {
+                account.games.push(seat.game_id);
+            }
+            if !account.seats.contains(&seat) {
+                account.seats.push(seat);
+            }
+        }
+        self.save(&accounts)
+    }
+
+    fn save(&self, accounts: &HashMap<String, Account>) -> Result<(), &'static str> {
+        if let Some(store) = &self.store {
+            if let Err(err) = store.save_document("accounts", accounts) {
+                error!("could not save accounts: {}", err);
+                return Err("could not save the account");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_are_found_whatever_their_case() {
+        let accounts = Accounts::new_accounts();
+        accounts.register("Alice", "correct horse").unwrap();
+        assert_eq!(
+            accounts.register("alice", "battery staple"),
+            Err("username taken")
+        );
+        assert!(accounts.exists("ALICE"));
+        // kept as typed
+        assert_eq!(accounts.account("alice").unwrap().username, "Alice");
+        assert!(accounts.login("aLiCe", "correct horse").is_ok());
+        assert!(accounts.login("alice", "battery staple").is_err());
+        let seat = AccountSeat {
+            game_id: Uuid::new_v4(),
+            player_id: Uuid::new_v4(),
+        };
+        accounts.set_seat("alice", Some(seat)).unwrap();
+        assert_eq!(accounts.account("Alice").unwrap().seat, Some(seat));
+    }
+}
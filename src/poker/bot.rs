@@ -0,0 +1,429 @@
+use crate::poker::game::{Card, Game, GamePlayState};
+use crate::poker::games_manager::GameState;
+use crate::poker::player::{PlayerAction, PlayerState};
+use poker::Evaluator;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// A computer player. It sees the table like a player at its seat does and answers with an
+// action and its amount, the same pair the perform_action endpoint takes.
+pub trait Bot: Send {
+    fn decide(&mut self, view: &GameState) -> (PlayerAction, u64);
+}
+
+// The bots built into the server, tables remember them by kind
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum BotKind {
+    Random,
+    CallingStation,
+    TightAggressive,
+}
+
+impl FromStr for BotKind {
+    type Err = &'static str;
+
+    // the names used in the API, "Random", "CallingStation" or "TightAggressive"
+    fn from_str(s: &str) -> Result<BotKind, &'static str> {
+        match s.trim() {
+            "Random" => Ok(BotKind::Random),
+            "CallingStation" => Ok(BotKind::CallingStation),
+            "TightAggressive" => Ok(BotKind::TightAggressive),
+            _ => Err("unknown bot"),
+        }
+    }
+}
+
+impl BotKind {
+    // the evaluator is costly to build, every bot of a table shares the game's
+    pub fn new_bot(self, seed: u64, evaluator: Arc<Evaluator>) -> Box<dyn Bot> {
+        match self {
+            BotKind::Random => Box::new(RandomBot::seeded(seed)),
+            BotKind::CallingStation => Box::new(CallingStation),
+            BotKind::TightAggressive => Box::new(TightAggressive::new_bot(evaluator)),
+        }
+    }
+}
+
+// chips the seat has to put in to call
+fn to_call(view: &GameState, seat: usize) -> u64 {
+    let max_bet = view
+        .players
+        .iter()
+        .flatten()
+        .map(|player| player.bet_amount)
+        .max()
+        .unwrap_or(0);
+    max_bet.saturating_sub(own_bet(view, seat))
+}
+
+fn own_bet(view: &GameState, seat: usize) -> u64 {
+    view.players[seat]
+        .as_ref()
+        .map_or(0, |player| player.bet_amount)
+}
+
+fn own_balance(view: &GameState, seat: usize) -> u64 {
+    view.players[seat]
+        .as_ref()
+        .map_or(0, |player| player.balance)
+}
+
+// Bet of `amount` more chips than calling takes, all in when the stack does not cover it
+fn raise(view: &GameState, seat: usize, amount: u64) -> (PlayerAction, u64) {
+    let chips = to_call(view, seat) + amount.max(view.big_blind);
+    match chips >= own_balance(view, seat) {
+        true => (PlayerAction::AllIn, 0),
+        false => (PlayerAction::Bet, chips),
+    }
+}
+
+// check when it is free, call when the stack covers it, all in otherwise
+fn check_or_call(view: &GameState, seat: usize) -> (PlayerAction, u64) {
+    let chips = to_call(view, seat);
+    if chips == 0 {
+        (PlayerAction::Check, 0)
+    } else if chips >= own_balance(view, seat) {
+        (PlayerAction::AllIn, 0)
+    } else {
+        (PlayerAction::Call, 0)
+    }
+}
+
+fn check_or_fold(view: &GameState, seat: usize) -> (PlayerAction, u64) {
+    match to_call(view, seat) {
+        0 => (PlayerAction::Check, 0),
+        _ => (PlayerAction::Fold, 0),
+    }
+}
+
+// Acts at random, mostly calling and now and then betting up to the pot
+pub struct RandomBot {
+    rng: StdRng,
+}
+
+impl RandomBot {
+    pub fn seeded(seed: u64) -> RandomBot {
+        RandomBot {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn decide(&mut self, view: &GameState) -> (PlayerAction, u64) {
+        let seat = match view.asker_seat {
+            None => return (PlayerAction::Fold, 0),
+            Some(seat) => seat,
+        };
+        match self.rng.gen_range(0..100) {
+            0..=14 => check_or_fold(view, seat),
+            15..=74 => check_or_call(view, seat),
+            75..=96 => {
+                let amount = self
+                    .rng
+                    .gen_range(view.big_blind..=view.pot.max(view.big_blind));
+                raise(view, seat, amount)
+            }
+            _ => (PlayerAction::AllIn, 0),
+        }
+    }
+}
+
+// Never folds and never bets, calls whatever it is asked to
+pub struct CallingStation;
+
+impl Bot for CallingStation {
+    fn decide(&mut self, view: &GameState) -> (PlayerAction, u64) {
+        match view.asker_seat {
+            None => (PlayerAction::Fold, 0),
+            Some(seat) => check_or_call(view, seat),
+        }
+    }
+}
+
+// Plays few hands and bets them hard: a starting hand chart before the flop, afterwards the
+// share of the opponent hands it beats on the board, against every opponent still in.
+pub struct TightAggressive {
+    evaluator: Arc<Evaluator>,
+}
+
+impl TightAggressive {
+    pub fn new_bot(evaluator: Arc<Evaluator>) -> TightAggressive {
+        TightAggressive { evaluator }
+    }
+
+    // share of the hands an opponent may hold that this one beats right now, ties count half
+    pub fn hand_strength(&self, cards: &[Card; 2], board: &[Card]) -> f64 {
+        let known: Vec<Card> = cards.iter().chain(board).copied().collect();
+        let ours = match self.evaluate(&known) {
+            None => return 0.0,
+            Some(eval) => eval,
+        };
+        let unseen: Vec<Card> = Card::ordered_deck()
+            .into_iter()
+            .filter(|card| !known.contains(card))
+            .collect();
+
+        let (mut beaten, mut total) = (0.0, 0.0);
+        for first in 0..unseen.len() {
+            for second in (first + 1)..unseen.len() {
+                let mut theirs: Vec<Card> = vec![unseen[first], unseen[second]];
+                theirs.extend_from_slice(board);
+                if let Some(eval) = self.evaluate(&theirs) {
+                    total += 1.0;
+                    if ours.is_better_than(eval) {
+                        beaten += 1.0;
+                    } else if ours.is_equal_to(eval) {
+                        beaten += 0.5;
+                    }
+                }
+            }
+        }
+        match total > 0.0 {
+            true => beaten / total,
+            false => 0.0,
+        }
+    }
+
+    fn evaluate(&self, cards: &[Card]) -> Option<poker::Eval> {
+        let cards: Vec<_> = cards.iter().map(|card| card.to_evaluate()).collect();
+        self.evaluator.evaluate(cards).ok()
+    }
+}
+
+// 0 to 10, roughly how playable a starting hand is
+fn starting_hand_score(cards: &[Card; 2]) -> u8 {
+    let (high, low) = match cards[0].rank() as u8 >= cards[1].rank() as u8 {
+        true => (cards[0].rank() as u8, cards[1].rank() as u8),
+        false => (cards[1].rank() as u8, cards[0].rank() as u8),
+    };
+    let suited = cards[0].color() == cards[1].color();
+    // ranks go from 0 for a two to 12 for an ace
+    let mut score = match high == low {
+        true => 5 + high / 3,
+        false => high / 3 + low / 4,
+    };
+    if suited {
+        score += 1;
+    }
+    if high != low && high - low == 1 {
+        score += 1;
+    }
+    score.min(10)
+}
+
+impl Bot for TightAggressive {
+    fn decide(&mut self, view: &GameState) -> (PlayerAction, u64) {
+        let seat = match view.asker_seat {
+            None => return (PlayerAction::Fold, 0),
+            Some(seat) => seat,
+        };
+        let cards = match view.personal_cards {
+            [Some(first), Some(second)] => [first, second],
+            _ => return check_or_fold(view, seat),
+        };
+        let chips = to_call(view, seat);
+        let board: Vec<Card> = view.community_cards.iter().flatten().copied().collect();
+
+        if board.is_empty() {
+            let score = starting_hand_score(&cards);
+            return if score >= 8 {
+                raise(view, seat, 3 * view.big_blind)
+            } else if score >= 5 && chips <= 3 * view.big_blind {
+                check_or_call(view, seat)
+            } else {
+                check_or_fold(view, seat)
+            };
+        }
+
+        let opponents = view
+            .players
+            .iter()
+            .flatten()
+            .filter(|player| {
+                player.seat_index as usize != seat
+                    && !matches!(player.state, PlayerState::Folded | PlayerState::Left)
+            })
+            .count()
+            .max(1);
+        let strength = self.hand_strength(&cards, &board).powi(opponents as i32);
+        let pot_odds = chips as f64 / (view.pot + chips).max(1) as f64;
+        if strength > 0.8 {
+            raise(view, seat, view.pot * 3 / 4)
+        } else if strength > 0.55 && chips == 0 {
+            raise(view, seat, view.pot / 2)
+        } else if strength > pot_odds {
+            check_or_call(view, seat)
+        } else {
+            check_or_fold(view, seat)
+        }
+    }
+}
+
+// Seat of a bot the game waits for: the player to act, or one that has to choose how many
+// times to run it
+pub fn bot_to_act(game: &Game, bots: &HashMap<usize, Box<dyn Bot>>) -> Option<usize> {
+    if game.game_state != GamePlayState::Started || !game.hand_in_progress() {
+        return None;
+    }
+    if let Some(vote) = game.runout_vote() {
+        return vote
+            .choices
+            .iter()
+            .find(|choice| choice.boards.is_none() && bots.contains_key(&choice.seat_index))
+            .map(|choice| choice.seat_index);
+    }
+    let seat = game.active_seat();
+    match bots.contains_key(&seat) {
+        true => Some(seat),
+        false => None,
+    }
+}
+
+// Lets the bot at the seat decide and plays its action, a refused action checks or folds
+pub fn play_bot(game: &mut Game, seat: usize, bot: &mut dyn Bot) {
+    let player_id = match game.players.iter().find(|(_, bot_seat)| **bot_seat == seat) {
+        None => return,
+        Some((player_id, _)) => *player_id,
+    };
+    if game.runout_vote().is_some() {
+        // a bot takes no chances with the runout
+        let _ = game.choose_runouts(seat, 1);
+        return;
+    }
+    let (action, amount) = bot.decide(&game.collect_state_data(player_id));
+    if game.player_action(seat, action, amount) == 0 {
+        let _ = game.player_action(seat, PlayerAction::Check, 0) != 0
+            || game.player_action(seat, PlayerAction::Fold, 0) != 0;
+    }
+}
+
+// Plays the bots until a human has to act or the hand is over, returns the actions taken
+pub fn play_bots(game: &mut Game, bots: &mut HashMap<usize, Box<dyn Bot>>) -> usize {
+    let hand_number = game.hand_number();
+    let mut actions = 0;
+    while let Some(seat) = bot_to_act(game, bots) {
+        let events = game.events().len();
+        if game.hand_number() != hand_number {
+            break;
+        }
+        play_bot(game, seat, bots.get_mut(&seat).unwrap().as_mut());
+        if game.events().len() == events {
+            // nothing the bot could do, the action clock takes over
+            break;
+        }
+        actions += 1;
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::deck::DeckSource;
+    use crate::poker::events::GameEvent;
+    use crate::poker::game::parse_cards;
+    use crate::poker::history::TableRecord;
+
+    // bots of the given kinds at their seats and humans at the others, seat 0 acts first
+    fn table(kinds: [Option<BotKind>; 3], deck: &str) -> (Game, HashMap<usize, Box<dyn Bot>>) {
+        let deck = DeckSource::stacked(vec![parse_cards(deck).unwrap()]).unwrap();
+        let mut game =
+            Game::new_game(TableRecord::new_table(3, 5, 10, 1000)).with_deck_source(deck);
+        for (seat, kind) in kinds.iter().enumerate() {
+            match kind {
+                Some(kind) => game.add_bot(Some(seat as u8), *kind).map(|_| ()),
+                None => game
+                    .join_game(seat as u8, &format!("player {}", seat), 0)
+                    .map(|_| ()),
+            }
+            .unwrap();
+        }
+        for (seat, kind) in kinds.iter().enumerate() {
+            if kind.is_none() {
+                game.set_ready(seat, true).unwrap();
+            }
+        }
+        let bots = game
+            .bot_seats()
+            .into_iter()
+            .map(|(seat, kind)| (seat, kind.new_bot(7, game.evaluator())))
+            .collect();
+        (game, bots)
+    }
+
+    // actions taken so far with the chips they put in
+    fn actions(game: &Game) -> Vec<(u8, PlayerAction, u64)> {
+        game.events()
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::ActionTaken {
+                    seat_index,
+                    action,
+                    amount,
+                } => Some((*seat_index, *action, *amount)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bots_are_named_like_the_api_does() {
+        assert_eq!("Random".parse(), Ok(BotKind::Random));
+        assert_eq!(" CallingStation ".parse(), Ok(BotKind::CallingStation));
+        assert_eq!("TightAggressive".parse(), Ok(BotKind::TightAggressive));
+        assert_eq!("tightaggressive".parse::<BotKind>(), Err("unknown bot"));
+    }
+
+    #[test]
+    fn a_calling_station_calls_then_waits_for_the_humans() {
+        let (mut game, mut bots) = table(
+            [Some(BotKind::CallingStation), None, None],
+            "AsAh KsKh 7c2d",
+        );
+        assert_eq!(bot_to_act(&game, &bots), Some(0));
+        assert_eq!(play_bots(&mut game, &mut bots), 1);
+        assert_eq!(actions(&game).last(), Some(&(0, PlayerAction::Call, 10)));
+        assert_eq!(bot_to_act(&game, &bots), None);
+        assert_eq!(game.active_seat(), 1);
+    }
+
+    #[test]
+    fn a_tight_aggressive_bot_raises_aces_and_folds_rags() {
+        let (mut game, mut bots) = table(
+            [Some(BotKind::TightAggressive), None, None],
+            "AsAh KsKh 7c2d",
+        );
+        play_bots(&mut game, &mut bots);
+        // calls the big blind and raises three more
+        assert_eq!(actions(&game).last(), Some(&(0, PlayerAction::Bet, 40)));
+
+        let (mut game, mut bots) = table(
+            [Some(BotKind::TightAggressive), None, None],
+            "7c2d KsKh AsAh",
+        );
+        play_bots(&mut game, &mut bots);
+        assert_eq!(actions(&game).last(), Some(&(0, PlayerAction::Fold, 0)));
+    }
+
+    #[test]
+    fn seeded_bots_play_a_hand_the_same_way_twice() {
+        let play = || {
+            let kinds = [
+                Some(BotKind::Random),
+                Some(BotKind::Random),
+                Some(BotKind::CallingStation),
+            ];
+            let (mut game, mut bots) = table(kinds, "AsAh KsKh 7c2d Kd8c3s 9h 4d");
+            play_bots(&mut game, &mut bots);
+            actions(&game)
+        };
+        let first = play();
+        assert!(first.len() > 3);
+        assert_eq!(first, play());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::poker::ledger::LedgerEntryKind;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum BuyInModel {
+    // single buy-in of initial_balance, busted players are out
+    #[default]
+    Freezeout,
+    // players may top up to max_buy_in between hands
+    Cash { max_buy_in: u64 },
+    // rebuys of initial_balance during the first rebuy_hands hands,
+    // followed by a single add-on of add_on chips on the last hand of that period
+    Tournament { rebuy_hands: u32, add_on: u64 },
+}
+
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+pub enum BuyInKind {
+    TopUp,
+    Rebuy,
+    AddOn,
+}
+
+impl BuyInKind {
+    pub const fn to_ledger(self) -> LedgerEntryKind {
+        match self {
+            BuyInKind::TopUp => LedgerEntryKind::TopUp,
+            BuyInKind::Rebuy => LedgerEntryKind::Rebuy,
+            BuyInKind::AddOn => LedgerEntryKind::AddOn,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PendingBuyIn {
+    pub seat_index: u8,
+    pub kind: LedgerEntryKind,
+    pub amount: u64,
+}
+
+impl BuyInModel {
+    pub fn validate(&self, game_initial_balance: u64) -> Result<(), &'static str> {
+        match *self {
+            BuyInModel::Cash { max_buy_in } if max_buy_in < game_initial_balance => {
+                Err("max buy-in lower than initial balance")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Works out how many chips a request is worth, `amount` is only used for top-ups.
+    // `hand_number` is the number of hands started so far.
+    pub fn chips_for(
+        &self,
+        kind: BuyInKind,
+        amount: u64,
+        stack: u64, // balance together with chips already waiting to be added
+        initial_balance: u64,
+        hand_number: u32,
+        add_ons_taken: usize,
+    ) -> Result<u64, &'static str> {
+        match (*self, kind) {
+            (BuyInModel::Cash { max_buy_in }, BuyInKind::TopUp) => {
+                if amount == 0 {
+                    return Err("top-up amount must be positive");
+                }
+                if stack + amount > max_buy_in {
+                    return Err("top-up would exceed max buy-in");
+                }
+                Ok(amount)
+            }
+            (BuyInModel::Tournament { rebuy_hands, .. }, BuyInKind::Rebuy) => {
+                if hand_number > rebuy_hands {
+                    return Err("rebuy period is over");
+                }
+                if stack > initial_balance {
+                    return Err("rebuy only allowed at or below the starting stack");
+                }
+                Ok(initial_balance)
+            }
+            (
+                BuyInModel::Tournament {
+                    rebuy_hands,
+                    add_on,
+                },
+                BuyInKind::AddOn,
+            ) => {
+                if hand_number != rebuy_hands {
+                    return Err("add-on only allowed at the end of the rebuy period");
+                }
+                if add_ons_taken > 0 {
+                    return Err("add-on already taken");
+                }
+                Ok(add_on)
+            }
+            _ => Err("buy-in type not allowed at this table"),
+        }
+    }
+}
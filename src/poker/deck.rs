@@ -0,0 +1,179 @@
+use crate::poker::game::Card;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+// Supplies the deck order of every hand for DeckSource::Custom
+pub trait DeckProvider: Send {
+    fn deck_for_hand(&mut self, hand_number: u32) -> [Card; 52];
+}
+
+// Where a game takes its decks from, chosen when the game is created
+#[derive(Clone, Default)]
+pub enum DeckSource {
+    // commit-reveal shuffle, see fairness.rs
+    #[default]
+    ProvablyFair,
+    // Knuth shuffle driven by a seeded rng, replays the same sequence of hands
+    Seeded(Box<StdRng>),
+    // the listed cards go on top of the deck, the rest follows in ordered deck order,
+    // hand n uses entry (n - 1) % len
+    Stacked(Vec<Vec<Card>>),
+    Custom(Arc<Mutex<dyn DeckProvider>>),
+}
+
+impl DeckSource {
+    pub fn seeded(seed: u64) -> DeckSource {
+        DeckSource::Seeded(Box::new(StdRng::seed_from_u64(seed)))
+    }
+
+    pub fn stacked(decks: Vec<Vec<Card>>) -> Result<DeckSource, &'static str> {
+        if decks.is_empty() {
+            return Err("no decks to stack");
+        }
+        for deck in &decks {
+            if deck.len() > 52 {
+                return Err("stacked deck longer than 52 cards");
+            }
+            for (idx, card) in deck.iter().enumerate() {
+                if deck[..idx].contains(card) {
+                    return Err("stacked deck contains a card twice");
+                }
+            }
+        }
+        Ok(DeckSource::Stacked(decks))
+    }
+
+    pub fn custom(provider: impl DeckProvider + 'static) -> DeckSource {
+        DeckSource::Custom(Arc::new(Mutex::new(provider)))
+    }
+
+    // None for the provably fair source, it needs the game's seeds. A custom provider's deck is
+    // checked, nothing is dealt from a deck missing a card.
+    pub fn deck_for_hand(&mut self, hand_number: u32) -> Result<Option<[Card; 52]>, &'static str> {
+        let deck = match self {
+            DeckSource::ProvablyFair => return Ok(None),
+            DeckSource::Seeded(rng) => {
+                let mut deck = Card::ordered_deck();
+                // Knuth shuffle
+                for n in 0..52 {
+                    let i = rng.gen_range(0..52 - n);
+                    deck.swap(i, 51 - n);
+                }
+                deck
+            }
+            DeckSource::Stacked(decks) => {
+                let top_cards = &decks[(hand_number.max(1) as usize - 1) % decks.len()];
+                stack_deck(top_cards)
+            }
+            DeckSource::Custom(provider) => {
+                let deck = provider.lock().unwrap().deck_for_hand(hand_number);
+                if !is_full_deck(&deck) {
+                    return Err("custom deck provider returned a broken deck");
+                }
+                deck
+            }
+        };
+        Ok(Some(deck))
+    }
+}
+
+pub fn stack_deck(top_cards: &[Card]) -> [Card; 52] {
+    let mut deck = Card::ordered_deck();
+    let rest: Vec<Card> = deck
+        .iter()
+        .filter(|card| !top_cards.contains(card))
+        .copied()
+        .collect();
+    for (idx, card) in top_cards.iter().chain(rest.iter()).enumerate() {
+        deck[idx] = *card;
+    }
+    deck
+}
+
+pub fn is_full_deck(deck: &[Card; 52]) -> bool {
+    Card::ordered_deck().iter().all(|card| deck.contains(card))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::game::parse_cards;
+
+    fn decks(source: &mut DeckSource, hands: u32) -> Vec<[Card; 52]> {
+        (1..=hands)
+            .map(|hand_number| source.deck_for_hand(hand_number).unwrap().unwrap())
+            .collect()
+    }
+
+    struct Ordered;
+
+    impl DeckProvider for Ordered {
+        fn deck_for_hand(&mut self, hand_number: u32) -> [Card; 52] {
+            let mut deck = Card::ordered_deck();
+            deck.rotate_left(hand_number as usize);
+            deck
+        }
+    }
+
+    struct MissingCard;
+
+    impl DeckProvider for MissingCard {
+        fn deck_for_hand(&mut self, _hand_number: u32) -> [Card; 52] {
+            let mut deck = Card::ordered_deck();
+            deck[51] = deck[0];
+            deck
+        }
+    }
+
+    #[test]
+    fn provably_fair_decks_come_from_the_game() {
+        assert!(DeckSource::default().deck_for_hand(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_shuffles() {
+        let shuffled = decks(&mut DeckSource::seeded(7), 3);
+        assert_eq!(shuffled, decks(&mut DeckSource::seeded(7), 3));
+        assert_ne!(shuffled, decks(&mut DeckSource::seeded(8), 3));
+        assert_ne!(shuffled[0], shuffled[1]);
+        assert!(shuffled.iter().all(is_full_deck));
+    }
+
+    #[test]
+    fn stacked_cards_go_on_top_in_turn() {
+        let top = parse_cards("AsAh KsKh").unwrap();
+        let other = parse_cards("2c").unwrap();
+        let mut source = DeckSource::stacked(vec![top.clone(), other.clone()]).unwrap();
+        let dealt = decks(&mut source, 3);
+        assert_eq!(dealt[0][..4], top[..]);
+        assert_eq!(dealt[1][..1], other[..]);
+        // the stack starts over once every deck was dealt
+        assert_eq!(dealt[2], dealt[0]);
+        assert!(dealt.iter().all(is_full_deck));
+    }
+
+    #[test]
+    fn broken_stacks_are_refused() {
+        let twice = parse_cards("AsAs").unwrap();
+        assert_eq!(
+            DeckSource::stacked(vec![twice]).err(),
+            Some("stacked deck contains a card twice")
+        );
+        assert_eq!(
+            DeckSource::stacked(Vec::new()).err(),
+            Some("no decks to stack")
+        );
+    }
+
+    #[test]
+    fn custom_decks_are_checked_before_dealing() {
+        let mut source = DeckSource::custom(Ordered);
+        let deck = source.deck_for_hand(2).unwrap().unwrap();
+        assert_eq!(deck[0], Card::ordered_deck()[2]);
+        assert_eq!(
+            DeckSource::custom(MissingCard).deck_for_hand(1).err(),
+            Some("custom deck provider returned a broken deck")
+        );
+    }
+}
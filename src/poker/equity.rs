@@ -0,0 +1,309 @@
+use crate::poker::game::{parse_cards, Card};
+use crate::poker::range::Range;
+use poker::{Eval, Evaluator};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// Every runout is enumerated when that takes at most this many hand evaluations,
+// otherwise MONTE_CARLO_SAMPLES random runouts are played
+pub const EXACT_EVALUATIONS: u64 = 500_000;
+pub const MONTE_CARLO_SAMPLES: u64 = 10_000;
+pub const MAX_HOLDINGS: usize = 10;
+
+// What is known of a player's hole cards
+#[derive(Clone, Debug, PartialEq)]
+pub enum Holding {
+    Cards([Card; 2]),
+    Combos(Vec<[Card; 2]>), // any of these, equally likely
+    Random,                 // any two cards
+}
+
+impl FromStr for Holding {
+    type Err = &'static str;
+
+    // "AsKd", "random", a list of hands like "AsKd, AhKh" or a range like "22+, AKs"
+    fn from_str(s: &str) -> Result<Holding, &'static str> {
+        if s.trim().eq_ignore_ascii_case("random") {
+            return Ok(Holding::Random);
+        }
+        let cards = match parse_cards(s) {
+            Ok(cards) => cards,
+            Err(_) => return Ok(Holding::Combos(s.parse::<Range>()?.combos)),
+        };
+        match cards.len() {
+            2 => Ok(Holding::Cards([cards[0], cards[1]])),
+            len if len > 2 && len % 2 == 0 => Ok(Holding::Combos(
+                cards.chunks(2).map(|hand| [hand[0], hand[1]]).collect(),
+            )),
+            _ => Err("a hand takes two cards"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Equity {
+    pub win: f64,    // share of the runouts won alone
+    pub tie: f64,    // share of the runouts split with others
+    pub equity: f64, // share of the pot won on average, a split counts its part
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct EquityResult {
+    pub players: Vec<Equity>, // in the order of the holdings
+    pub exact: bool,
+    pub runouts: u64, // enumerated or sampled
+}
+
+// Hole cards turned face up when the betting closed before the river, with their chances then
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TabledHand {
+    pub seat_index: u8,
+    pub cards: [Card; 2],
+    pub equity: Equity,
+}
+
+// cards as bits of a u64, to tell quickly whether two sets share one
+fn mask(cards: &[Card]) -> u64 {
+    cards.iter().fold(0, |mask, card| {
+        mask | 1 << (card.color() as u64 * 13 + card.rank() as u64)
+    })
+}
+
+struct Tally {
+    wins: Vec<f64>,
+    ties: Vec<f64>,
+    shares: Vec<f64>,
+    runouts: u64,
+}
+
+impl Tally {
+    fn new_tally(players: usize) -> Tally {
+        Tally {
+            wins: vec![0.0; players],
+            ties: vec![0.0; players],
+            shares: vec![0.0; players],
+            runouts: 0,
+        }
+    }
+
+    fn result(self, exact: bool) -> EquityResult {
+        let runouts = self.runouts.max(1) as f64;
+        EquityResult {
+            players: (0..self.wins.len())
+                .map(|player| Equity {
+                    win: self.wins[player] / runouts,
+                    tie: self.ties[player] / runouts,
+                    equity: self.shares[player] / runouts,
+                })
+                .collect(),
+            exact,
+            runouts: self.runouts,
+        }
+    }
+}
+
+struct Runouts<'a> {
+    evaluator: &'a Evaluator,
+    combos: Vec<Vec<[Card; 2]>>, // of every player, without the cards known to be elsewhere
+    board: Vec<Card>,
+    known: u64, // board and dead cards
+    tally: Tally,
+}
+
+impl<'a> Runouts<'a> {
+    // splits the pot of one complete board among the best hands
+    fn score(&mut self, hands: &[[Card; 2]], board: &[Card]) {
+        let mut evals: Vec<Eval> = Vec::with_capacity(hands.len());
+        for hand in hands {
+            let cards: Vec<_> = hand
+                .iter()
+                .chain(board)
+                .map(|card| card.to_evaluate())
+                .collect();
+            match self.evaluator.evaluate(cards) {
+                Ok(eval) => evals.push(eval),
+                Err(_) => return,
+            }
+        }
+        let mut best = evals[0];
+        for eval in &evals[1..] {
+            if eval.is_better_than(best) {
+                best = *eval;
+            }
+        }
+        let winners: Vec<usize> = (0..evals.len())
+            .filter(|player| evals[*player].is_equal_to(best))
+            .collect();
+        for player in &winners {
+            match winners.len() {
+                1 => self.tally.wins[*player] += 1.0,
+                _ => self.tally.ties[*player] += 1.0,
+            }
+            self.tally.shares[*player] += 1.0 / winners.len() as f64;
+        }
+        self.tally.runouts += 1;
+    }
+
+    // every hand each player may hold, then every way to complete the board
+    fn enumerate(&mut self, hands: &mut Vec<[Card; 2]>, used: u64) {
+        if hands.len() == self.combos.len() {
+            let unseen: Vec<Card> = Card::ordered_deck()
+                .into_iter()
+                .filter(|card| used & mask(&[*card]) == 0)
+                .collect();
+            let mut board = self.board.clone();
+            self.complete_board(hands, &unseen, 0, &mut board);
+            return;
+        }
+        for idx in 0..self.combos[hands.len()].len() {
+            let hand = self.combos[hands.len()][idx];
+            if used & mask(&hand) != 0 {
+                continue;
+            }
+            hands.push(hand);
+            self.enumerate(hands, used | mask(&hand));
+            hands.pop();
+        }
+    }
+
+    fn complete_board(
+        &mut self,
+        hands: &[[Card; 2]],
+        unseen: &[Card],
+        from: usize,
+        board: &mut Vec<Card>,
+    ) {
+        if board.len() == 5 {
+            self.score(hands, board);
+            return;
+        }
+        for idx in from..unseen.len() {
+            board.push(unseen[idx]);
+            self.complete_board(hands, unseen, idx + 1, board);
+            board.pop();
+        }
+    }
+
+    fn sample(&mut self, samples: u64, rng: &mut impl Rng) -> Result<(), &'static str> {
+        for _ in 0..samples {
+            // hands are drawn again until nobody holds a card of someone else
+            let mut hands = Vec::with_capacity(self.combos.len());
+            let mut used = self.known;
+            let mut attempts = 0;
+            while hands.len() < self.combos.len() {
+                let hand = *self.combos[hands.len()].choose(rng).unwrap();
+                if used & mask(&hand) == 0 {
+                    used |= mask(&hand);
+                    hands.push(hand);
+                    continue;
+                }
+                attempts += 1;
+                if attempts > 1000 {
+                    return Err("the hands leave each other no cards");
+                }
+                hands.clear();
+                used = self.known;
+            }
+            let mut unseen: Vec<Card> = Card::ordered_deck()
+                .into_iter()
+                .filter(|card| used & mask(&[*card]) == 0)
+                .collect();
+            let missing = 5 - self.board.len();
+            let (dealt, _) = unseen.partial_shuffle(rng, missing);
+            let mut board = self.board.clone();
+            board.extend_from_slice(dealt);
+            self.score(&hands, &board);
+        }
+        Ok(())
+    }
+}
+
+// n choose k, saturating
+fn combinations(n: u64, k: u64) -> u64 {
+    (0..k).fold(1u64, |total, idx| total.saturating_mul(n - idx) / (idx + 1))
+}
+
+// Chances of every holding to win on the board, partial or empty, with the dead cards out of the
+// deck. Exact when few enough runouts are possible, by Monte Carlo otherwise.
+pub fn equity(
+    evaluator: &Evaluator,
+    holdings: &[Holding],
+    board: &[Card],
+    dead: &[Card],
+    rng: &mut impl Rng,
+) -> Result<EquityResult, &'static str> {
+    if holdings.len() < 2 || holdings.len() > MAX_HOLDINGS {
+        return Err("equity takes 2 to 10 hands");
+    }
+    if board.len() > 5 {
+        return Err("a board has at most 5 cards");
+    }
+    let mut known: Vec<Card> = board.iter().chain(dead).copied().collect();
+    for holding in holdings {
+        if let Holding::Cards(cards) = holding {
+            known.extend_from_slice(cards);
+        }
+    }
+    if mask(&known).count_ones() as usize != known.len() {
+        return Err("a card is used twice");
+    }
+
+    let board_and_dead = mask(board) | mask(dead);
+    let mut combos = Vec::with_capacity(holdings.len());
+    for holding in holdings {
+        // other players' cards are only removed from ranges, they are part of the hand otherwise
+        let blocked = match holding {
+            Holding::Cards(_) => board_and_dead,
+            _ => mask(&known),
+        };
+        let possible: Vec<[Card; 2]> = match holding {
+            Holding::Cards(cards) => vec![*cards],
+            Holding::Combos(hands) => hands.clone(),
+            Holding::Random => {
+                let deck = Card::ordered_deck();
+                let mut hands = Vec::with_capacity(52 * 51 / 2);
+                for first in 0..deck.len() {
+                    for second in (first + 1)..deck.len() {
+                        hands.push([deck[first], deck[second]]);
+                    }
+                }
+                hands
+            }
+        };
+        let possible: Vec<[Card; 2]> = possible
+            .into_iter()
+            .filter(|hand| hand[0] != hand[1] && mask(hand) & blocked == 0)
+            .collect();
+        if possible.is_empty() {
+            return Err("no hand is left in a range once the known cards are out");
+        }
+        combos.push(possible);
+    }
+
+    let unseen = 52 - known.len() as u64;
+    let evaluations = combos
+        .iter()
+        .fold(
+            combinations(unseen, 5 - board.len() as u64),
+            |total, hands| total.saturating_mul(hands.len() as u64),
+        )
+        .saturating_mul(holdings.len() as u64);
+    let mut runouts = Runouts {
+        evaluator,
+        combos,
+        board: board.to_vec(),
+        known: board_and_dead,
+        tally: Tally::new_tally(holdings.len()),
+    };
+    let exact = evaluations <= EXACT_EVALUATIONS;
+    match exact {
+        true => runouts.enumerate(&mut Vec::with_capacity(holdings.len()), board_and_dead),
+        false => runouts.sample(MONTE_CARLO_SAMPLES, rng)?,
+    }
+    if runouts.tally.runouts == 0 {
+        return Err("the hands leave each other no cards");
+    }
+    Ok(runouts.tally.result(exact))
+}
@@ -0,0 +1,123 @@
+use crate::poker::bot::BotKind;
+use crate::poker::buy_in::PendingBuyIn;
+use crate::poker::equity::TabledHand;
+use crate::poker::fairness::{ServerSeed, ShuffleProof};
+use crate::poker::game::{Card, GamePhase};
+use crate::poker::games_manager::{HandSummary, PotResult};
+use crate::poker::history::{HandAction, ShowdownRecord, TableRecord};
+use crate::poker::ledger::ChipAlert;
+use crate::poker::player::PlayerAction;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Everything that happened at a table, in order. Commands on Game decide which events happen,
+// applying them is the only way the game state changes, so the log alone rebuilds a game.
+// The log holds the server seeds and decks, it is not meant to be shown to players as is.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum GameEvent {
+    GameCreated {
+        table: TableRecord,
+        server_seed: ServerSeed,
+    },
+    PlayerJoined {
+        player_id: Uuid,
+        seat_index: u8,
+        nickname: String,
+        appearance_type: u8,
+        balance: u64,
+    },
+    // the player who just joined at the seat is a computer player
+    BotSeated {
+        seat_index: u8,
+        kind: BotKind,
+    },
+    ReadyChanged {
+        seat_index: u8,
+        ready: bool,
+    },
+    ClientSeedSet {
+        seat_index: u8,
+        seed: Option<String>,
+    },
+    BuyInQueued {
+        buy_in: PendingBuyIn,
+    },
+    // a queued buy-in is taken off the queue when it is applied
+    ChipsBought {
+        buy_in: PendingBuyIn,
+    },
+    CashedOut {
+        player_id: Uuid,
+        seat_index: u8,
+    },
+    GameStarted,
+    GameEnded,
+    // the table deals the next hand at this unix time, chips can be bought until then
+    NextHandScheduled {
+        at: u64,
+    },
+    // from the next hand on
+    BlindsRaised {
+        small_blind: u64,
+        big_blind: u64,
+        raised_at: u64,
+    },
+    GamePaused,
+    GameResumed,
+    HandStarted {
+        hand_number: u32,
+        dealer_seat: usize,
+        deck: Vec<Card>,
+        shuffle_proof: Option<ShuffleProof>,
+        next_server_seed: Option<ServerSeed>, // replaces the seed used up by this hand
+        started_at: u64,
+    },
+    BlindPosted {
+        seat_index: u8,
+        blind: HandAction,
+        amount: u64, // less than the blind when it puts the player all in
+    },
+    ActionTaken {
+        seat_index: u8,
+        action: PlayerAction,
+        amount: u64, // chips put in
+    },
+    TurnPassed {
+        seat_index: usize,
+    },
+    BetReturned {
+        seat_index: u8,
+        amount: u64,
+    },
+    BetsCollected,
+    StreetDealt {
+        street: GamePhase,
+        cards: Vec<Card>, // new community cards, for listeners, the deck already holds them
+    },
+    // betting closed before the river, the players still in show their cards
+    HandsTabled {
+        hands: Vec<TabledHand>,
+    },
+    RunoutVoteOpened {
+        seats: Vec<usize>,
+    },
+    RunoutChosen {
+        seat_index: usize,
+        boards: u8,
+    },
+    BoardsRun {
+        boards: u8,
+    },
+    // the rest of the board is turned over for the showdown
+    BoardCompleted,
+    PotAwarded {
+        pot: PotResult,
+    },
+    HandEnded {
+        summary: HandSummary,
+        showdown: Vec<ShowdownRecord>,
+    },
+    ChipAlertRaised {
+        alert: ChipAlert,
+    },
+}
@@ -0,0 +1,162 @@
+use crate::poker::game::Card;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+pub const MAX_CLIENT_SEED_LENGTH: usize = 64;
+
+// Commit-reveal shuffling: the hash of the server seed is published before a hand,
+// players may add client seeds, the deck is derived from all of them and the server
+// seed is revealed once the hand is over so anyone can recompute the deck order.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServerSeed {
+    seed: [u8; 32],
+}
+
+impl ServerSeed {
+    pub fn new_seed() -> ServerSeed {
+        let mut seed = [0u8; 32];
+        thread_rng().fill_bytes(&mut seed);
+        ServerSeed { seed }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.seed
+    }
+
+    pub fn commitment(&self) -> String {
+        hex::encode(Sha256::digest(self.seed))
+    }
+
+    pub fn reveal(&self) -> String {
+        hex::encode(self.seed)
+    }
+}
+
+// keeps the seed itself out of logs
+impl fmt::Debug for ServerSeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ServerSeed({})", self.commitment())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ShuffleProof {
+    pub hand_number: u32,
+    pub commitment: String, // sha256 of the server seed, published before the hand
+    pub server_seed: String, // hex, revealed after the hand
+    pub client_seeds: Vec<String>, // in seat order
+}
+
+// sha256 over the server seed followed by every client seed prefixed with its length
+pub fn combined_seed(server_seed: &[u8], client_seeds: &[String]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(server_seed);
+    for client_seed in client_seeds {
+        hasher.update((client_seed.len() as u32).to_be_bytes());
+        hasher.update(client_seed.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+struct SeedStream {
+    seed: [u8; 32],
+    counter: u64,
+}
+
+impl SeedStream {
+    fn next_u64(&mut self) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.seed);
+        hasher.update(self.counter.to_be_bytes());
+        self.counter += 1;
+        let block = hasher.finalize();
+        u64::from_be_bytes(block[..8].try_into().unwrap())
+    }
+
+    // uniform in 0..bound, rejection sampling keeps it free of modulo bias
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - (u64::MAX % bound);
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+// Knuth shuffle of the ordered deck driven by the combined seed
+pub fn shuffled_deck(server_seed: &[u8], client_seeds: &[String]) -> [Card; 52] {
+    let mut deck = Card::ordered_deck();
+    let mut stream = SeedStream {
+        seed: combined_seed(server_seed, client_seeds),
+        counter: 0,
+    };
+    for n in 0..52 {
+        let i = stream.below(52 - n as u64) as usize;
+        deck.swap(i, 51 - n);
+    }
+    deck
+}
+
+// Recomputes the deck of a finished hand from its revealed proof, fails if the revealed
+// server seed does not match the commitment published before the hand.
+pub fn verify_shuffle(proof: &ShuffleProof) -> Result<[Card; 52], &'static str> {
+    let server_seed = hex::decode(&proof.server_seed).map_err(|_| "server seed is not hex")?;
+    if hex::encode(Sha256::digest(&server_seed)) != proof.commitment.to_lowercase() {
+        return Err("server seed does not match the commitment");
+    }
+    Ok(shuffled_deck(&server_seed, &proof.client_seeds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof(server_seed: &ServerSeed, client_seeds: &[&str]) -> ShuffleProof {
+        ShuffleProof {
+            hand_number: 1,
+            commitment: server_seed.commitment(),
+            server_seed: server_seed.reveal(),
+            client_seeds: client_seeds.iter().map(|seed| seed.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn a_revealed_seed_recomputes_the_dealt_deck() {
+        let server_seed = ServerSeed::new_seed();
+        let client_seeds = vec!["alice".to_string(), "bob".to_string()];
+        let dealt = shuffled_deck(server_seed.bytes(), &client_seeds);
+        assert!(Card::ordered_deck().iter().all(|card| dealt.contains(card)));
+
+        let proof = proof(&server_seed, &["alice", "bob"]);
+        assert_eq!(verify_shuffle(&proof), Ok(dealt));
+    }
+
+    #[test]
+    fn a_tampered_seed_does_not_verify() {
+        let server_seed = ServerSeed::new_seed();
+        let dealt = shuffled_deck(
+            server_seed.bytes(),
+            &["alice".to_string(), "bob".to_string()],
+        );
+
+        // another client seed gives another deck
+        let tampered = proof(&server_seed, &["alice", "mallory"]);
+        assert_ne!(verify_shuffle(&tampered).unwrap(), dealt);
+        // so do the same seeds in another order
+        let reordered = proof(&server_seed, &["bob", "alice"]);
+        assert_ne!(verify_shuffle(&reordered).unwrap(), dealt);
+        // another server seed is caught by the commitment
+        let mut swapped = proof(&server_seed, &["alice", "bob"]);
+        swapped.server_seed = ServerSeed::new_seed().reveal();
+        assert_eq!(
+            verify_shuffle(&swapped),
+            Err("server seed does not match the commitment")
+        );
+        swapped.server_seed = "not hex".to_string();
+        assert_eq!(verify_shuffle(&swapped), Err("server seed is not hex"));
+    }
+}
@@ -1,12 +1,30 @@
 use super::player::PlayerState;
-use crate::poker::games_manager::GameState;
+use crate::poker::bot::BotKind;
+use crate::poker::buy_in::{BuyInKind, BuyInModel, PendingBuyIn};
+use crate::poker::deck::DeckSource;
+use crate::poker::equity::{equity, Holding, TabledHand};
+use crate::poker::events::GameEvent;
+use crate::poker::fairness::{shuffled_deck, ServerSeed, ShuffleProof, MAX_CLIENT_SEED_LENGTH};
+use crate::poker::games_manager::{GameState, HandSummary, PotResult};
+use crate::poker::hints::{training_hints, TrainingHints};
+use crate::poker::history::{HandAction, HandHistory, SeatRecord, ShowdownRecord, TableRecord};
+use crate::poker::ledger::{ChipAlert, ChipLedger, LedgerEntryKind};
 use crate::poker::player::{Player, PlayerAction, PlayerData};
+use crate::poker::pot::{build_pots, Contribution};
+use crate::poker::reaper::unix_now;
+use crate::poker::runout::RunoutVote;
+use log::{debug, error, info, warn};
 use poker::{
     Card as EvaluatorCard, Eval, Evaluator, Rank as EvaluatorRank, Suit as EvaluatorColor,
 };
-use rand::{thread_rng, Rng};
-use serde::Serialize;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -14,20 +32,48 @@ pub struct Game {
     pub players: HashMap<Uuid, usize>, // map player_id to his seat index
     pub players_by_seats: Vec<Option<Player>>,
     nicknames: Vec<Option<String>>,
+    bots: Vec<Option<BotKind>>, // indexed by seats, None for human players
     pub max_players: usize,
     pub small_blind: u64,
     pub big_blind: u64, // typically 2 * small_blind, but not always
     pub initial_balance: u64,
+    pub buy_in_model: BuyInModel,
+    pub max_boards: u8, // how many times an all-in hand may be run out, 1 disables it
+    pub rabbit_hunt: bool, // reveal the rest of the board when a hand ends before the river
+    pub action_clock: u64, // seconds a player has to act, 0 for no limit
+    pub training: bool, // every player sees hints about their own hand
+    pub hand_pause: u64, // seconds between hands to buy chips in, 0 deals the next hand right away
+    pub level_duration: u64, // seconds before the blinds double, 0 keeps them
     pub game_state: GamePlayState,
+    blind_level: u32,              // times the blinds went up
+    level_started_at: Option<u64>, // unix time, the first level starts with the first hand
+    next_hand_at: Option<u64>,     // unix time, set while the table pauses between hands
+    hand_number: u32,              // hands started so far
+    ledger: ChipLedger,
+    pending_buy_ins: Vec<PendingBuyIn>, // applied at the start of the next hand
     deck: [Card; 52],
+    deck_source: DeckSource,
+    server_seed: ServerSeed, // seed of the next hand, only its commitment is public
+    client_seeds: Vec<Option<String>>, // indexed by seats, mixed into every shuffle
+    shuffle_proof: Option<ShuffleProof>, // current hand, revealed in the hand summary
     community_cards: [Option<Card>; 5],
     community_cards_shown: usize,
+    extra_boards: Vec<[Option<Card>; 5]>,
+    next_deck_card: usize, // first card of the deck not dealt yet
+    runout_vote: Option<RunoutVote>,
+    tabled_hands: Vec<TabledHand>, // current hand, once the betting closed before the river
+    last_hand: Option<HandSummary>,
+    current_hand: Option<HandHistory>,
+    hand_histories: Vec<HandHistory>,
+    final_hand: Option<u32>, // the game ends after this hand, used by replays
+    events: Vec<GameEvent>,  // every change to the game, in order
+    paused: bool,            // no new hands are dealt, the current one plays out
     dealer_seat: usize,
     small_blind_seat: usize,
     big_blind_seat: usize,
     active_player: usize,
     game_phase: GamePhase,
-    evaluator: Evaluator,
+    evaluator: Arc<Evaluator>, // shared with the bots of the table
 }
 
 fn next_player(
@@ -52,236 +98,58 @@ fn next_player(
     0
 }
 
+// deck of a hand, with its proof and the seed replacing the used up one when provably fair
+type ShuffledDeck = ([Card; 52], Option<ShuffleProof>, Option<ServerSeed>);
+
 impl Game {
-    pub fn new_game(
-        max_players: usize,
-        small_blind: u64,
-        big_blind: u64,
-        initial_balance: u64,
-    ) -> Game {
+    pub fn new_game(table: TableRecord) -> Game {
+        Game::created(table, ServerSeed::new_seed(), Arc::new(Evaluator::new()))
+    }
+
+    fn created(table: TableRecord, server_seed: ServerSeed, evaluator: Arc<Evaluator>) -> Game {
+        let max_players = table.max_players;
         let players: HashMap<Uuid, usize> = HashMap::with_capacity(max_players);
         let players_by_seats = vec![None; max_players];
         let nicknames = vec![None; max_players];
-        let deck: [Card; 52] = [
-            Card {
-                color: Color::Spades,
-                rank: Rank::Two,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Three,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Four,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Five,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Six,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Seven,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Eight,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Nine,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Ten,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Jack,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Queen,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::King,
-            },
-            Card {
-                color: Color::Spades,
-                rank: Rank::Ace,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Two,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Three,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Four,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Five,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Six,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Seven,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Eight,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Nine,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Ten,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Jack,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Queen,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::King,
-            },
-            Card {
-                color: Color::Hearts,
-                rank: Rank::Ace,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Two,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Three,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Four,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Five,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Six,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Seven,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Eight,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Nine,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Ten,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Jack,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Queen,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::King,
-            },
-            Card {
-                color: Color::Diamonds,
-                rank: Rank::Ace,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Two,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Three,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Four,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Five,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Six,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Seven,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Eight,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Nine,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Ten,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Jack,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Queen,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::King,
-            },
-            Card {
-                color: Color::Clubs,
-                rank: Rank::Ace,
-            },
-        ];
+        let bots = vec![None; max_players];
+        let deck = Card::ordered_deck();
         let community_cards: [Option<Card>; 5] = [None, None, None, None, None];
         Game {
             players,
-            small_blind,
-            big_blind,
-            initial_balance,
+            small_blind: table.small_blind,
+            big_blind: table.big_blind,
+            initial_balance: table.initial_balance,
+            buy_in_model: table.buy_in_model,
+            max_boards: table.max_boards,
+            rabbit_hunt: table.rabbit_hunt,
+            action_clock: table.action_clock,
+            training: table.training,
+            hand_pause: table.hand_pause,
+            level_duration: table.level_duration,
+            blind_level: 0,
+            level_started_at: None,
+            next_hand_at: None,
             game_state: GamePlayState::NotStarted,
+            hand_number: 0,
+            ledger: ChipLedger::new_ledger(),
+            pending_buy_ins: Vec::new(),
             deck,
+            deck_source: DeckSource::ProvablyFair,
+            server_seed: server_seed.clone(),
+            client_seeds: vec![None; max_players],
+            shuffle_proof: None,
             community_cards,
             community_cards_shown: 0,
+            extra_boards: Vec::new(),
+            next_deck_card: 0,
+            runout_vote: None,
+            tabled_hands: Vec::new(),
+            last_hand: None,
+            current_hand: None,
+            hand_histories: Vec::new(),
+            final_hand: None,
+            events: vec![GameEvent::GameCreated { table, server_seed }],
+            paused: false,
             players_by_seats,
             dealer_seat: 69,
             small_blind_seat: 420,
@@ -289,8 +157,273 @@ impl Game {
             active_player: 666,
             max_players,
             game_phase: GamePhase::PreFlop,
-            evaluator: Evaluator::new(),
+            evaluator,
             nicknames,
+            bots,
+        }
+    }
+
+    // Rebuilds a game from its event log. Decks are taken from the log, a seeded or custom
+    // deck source has to be set again for the hands still to come.
+    pub fn from_events(events: &[GameEvent]) -> Result<Game, &'static str> {
+        let mut game = match events.first() {
+            Some(GameEvent::GameCreated { table, server_seed }) => {
+                Game::created(*table, server_seed.clone(), Arc::new(Evaluator::new()))
+            }
+            _ => return Err("event log does not start with the game creation"),
+        };
+        for event in &events[1..] {
+            game.emit(event.clone());
+        }
+        Ok(game)
+    }
+
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    fn emit(&mut self, event: GameEvent) {
+        self.apply(&event);
+        self.events.push(event);
+    }
+
+    // The only place the state of the game changes. Applying has to give the same result every
+    // time, decisions and randomness belong to the commands emitting the events.
+    fn apply(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::GameCreated { .. } => (), // set up by the constructor
+            GameEvent::PlayerJoined {
+                player_id,
+                seat_index,
+                nickname,
+                appearance_type,
+                balance,
+            } => {
+                let player = Player::new_player(*seat_index, *balance, *appearance_type);
+                self.players.insert(*player_id, *seat_index as usize);
+                self.players_by_seats[*seat_index as usize] = Some(player);
+                self.nicknames[*seat_index as usize] = Some(nickname.clone());
+                self.ledger.seat(*seat_index, *player_id);
+                self.ledger.record(
+                    self.hand_number,
+                    *seat_index,
+                    LedgerEntryKind::BuyIn,
+                    *balance,
+                );
+            }
+            GameEvent::ReadyChanged { seat_index, ready } => {
+                let player: &mut Player = self.players_by_seats[*seat_index as usize]
+                    .as_mut()
+                    .unwrap();
+                player.set_ready(*ready);
+            }
+            GameEvent::BotSeated { seat_index, kind } => {
+                self.bots[*seat_index as usize] = Some(*kind);
+            }
+            GameEvent::ClientSeedSet { seat_index, seed } => {
+                self.client_seeds[*seat_index as usize] = seed.clone();
+            }
+            GameEvent::BuyInQueued { buy_in } => self.pending_buy_ins.push(*buy_in),
+            GameEvent::ChipsBought { buy_in } => {
+                if let Some(idx) = self
+                    .pending_buy_ins
+                    .iter()
+                    .position(|pending| pending == buy_in)
+                {
+                    self.pending_buy_ins.remove(idx);
+                }
+                self.apply_buy_in(*buy_in);
+            }
+            GameEvent::CashedOut {
+                player_id,
+                seat_index,
+            } => {
+                self.players.remove(player_id);
+                let player = self.players_by_seats[*seat_index as usize].take().unwrap();
+                self.nicknames[*seat_index as usize] = None;
+                self.pending_buy_ins
+                    .retain(|pending| pending.seat_index != *seat_index);
+                self.ledger.record(
+                    self.hand_number,
+                    *seat_index,
+                    LedgerEntryKind::CashOut,
+                    player.balance,
+                );
+            }
+            GameEvent::NextHandScheduled { at } => self.next_hand_at = Some(*at),
+            GameEvent::BlindsRaised {
+                small_blind,
+                big_blind,
+                raised_at,
+            } => {
+                self.small_blind = *small_blind;
+                self.big_blind = *big_blind;
+                self.blind_level += 1;
+                self.level_started_at = Some(*raised_at);
+            }
+            GameEvent::GamePaused => self.paused = true,
+            GameEvent::GameResumed => self.paused = false,
+            GameEvent::GameStarted => self.game_state = GamePlayState::Started,
+            GameEvent::GameEnded => self.game_state = GamePlayState::Ended,
+            GameEvent::HandStarted {
+                hand_number,
+                dealer_seat,
+                deck,
+                shuffle_proof,
+                next_server_seed,
+                started_at,
+            } => {
+                self.hand_number = *hand_number;
+                self.next_hand_at = None;
+                self.level_started_at.get_or_insert(*started_at);
+                self.deck.copy_from_slice(deck);
+                self.shuffle_proof = shuffle_proof.clone();
+                if let Some(server_seed) = next_server_seed {
+                    self.server_seed = server_seed.clone();
+                }
+                self.deal_cards();
+                self.tabled_hands.clear();
+                self.dealer_seat = *dealer_seat;
+                self.active_player = self.dealer_seat;
+                self.set_players_active(true);
+                self.reset_players();
+                self.sit_out_busted_players();
+                self.set_next_active_player();
+                self.begin_hand_history(*started_at);
+            }
+            GameEvent::BlindPosted {
+                seat_index,
+                blind,
+                amount,
+            } => {
+                let seat = *seat_index as usize;
+                let player: &mut Player = self.players_by_seats[seat].as_mut().unwrap();
+                let _ = player.perform_action(PlayerAction::Bet, *amount);
+                self.ledger
+                    .record(self.hand_number, *seat_index, LedgerEntryKind::Bet, *amount);
+                self.record_hand_action(seat, *blind, *amount);
+                match blind {
+                    HandAction::SmallBlind => self.small_blind_seat = seat,
+                    _ => self.big_blind_seat = seat,
+                }
+                if let Some(history) = &mut self.current_hand {
+                    history.small_blind_seat = self.small_blind_seat;
+                    history.big_blind_seat = self.big_blind_seat;
+                }
+                self.active_player = seat;
+                self.set_next_active_player();
+            }
+            GameEvent::ActionTaken {
+                seat_index,
+                action,
+                amount,
+            } => {
+                let seat = *seat_index as usize;
+                let max_bet = self.max_bet();
+                let player: &mut Player = self.players_by_seats[seat].as_mut().unwrap();
+                let _ = match action {
+                    PlayerAction::Call | PlayerAction::Check => {
+                        player.perform_action(*action, max_bet)
+                    }
+                    _ => player.perform_action(*action, *amount),
+                };
+                self.ledger
+                    .record(self.hand_number, *seat_index, LedgerEntryKind::Bet, *amount);
+                self.record_hand_action(seat, HandAction::from_player_action(*action), *amount);
+            }
+            GameEvent::TurnPassed { seat_index } => self.active_player = *seat_index,
+            GameEvent::BetReturned { seat_index, amount } => {
+                let seat = *seat_index as usize;
+                match &mut self.players_by_seats[seat] {
+                    Some(pl) => pl.return_bet(*amount),
+                    None => return,
+                }
+                self.ledger.record(
+                    self.hand_number,
+                    *seat_index,
+                    LedgerEntryKind::Return,
+                    *amount,
+                );
+                self.record_hand_action(seat, HandAction::UncalledBetReturned, *amount);
+            }
+            GameEvent::BetsCollected => {
+                for seat in 0..self.max_players {
+                    if let Some(pl) = &mut self.players_by_seats[seat] {
+                        let collected = pl.current_bet;
+                        let _ = pl.collect_bet();
+                        self.ledger.record(
+                            self.hand_number,
+                            seat as u8,
+                            LedgerEntryKind::Collect,
+                            collected,
+                        );
+                    }
+                }
+            }
+            GameEvent::StreetDealt { street, .. } => {
+                self.active_player = self.dealer_seat;
+                self.set_next_active_player();
+                self.game_phase = *street;
+                self.set_players_active(false);
+                self.community_cards_shown = match street {
+                    GamePhase::PreFlop => 0,
+                    GamePhase::Flop => 3,
+                    GamePhase::Turn => 4,
+                    GamePhase::River => 5,
+                };
+            }
+            GameEvent::HandsTabled { hands } => self.tabled_hands = hands.clone(),
+            GameEvent::RunoutVoteOpened { seats } => {
+                self.runout_vote = Some(RunoutVote::new_vote(seats.clone(), self.max_boards));
+            }
+            GameEvent::RunoutChosen { seat_index, boards } => {
+                if let Some(vote) = &mut self.runout_vote {
+                    let _ = vote.choose(*seat_index, *boards);
+                }
+                self.record_hand_action(*seat_index, HandAction::RunIt, *boards as u64);
+            }
+            GameEvent::BoardsRun { boards } => {
+                self.runout_vote = None;
+                self.deal_extra_boards(*boards);
+            }
+            GameEvent::BoardCompleted => {
+                self.game_phase = GamePhase::River;
+                self.community_cards_shown = 5;
+            }
+            GameEvent::PotAwarded { pot } => {
+                let share = pot.amount / pot.winners.len() as u64;
+                let mut odd_chips = pot.amount % pot.winners.len() as u64;
+                for winner_seat in &pot.winners {
+                    let mut winnings = share;
+                    if odd_chips > 0 {
+                        winnings += 1;
+                        odd_chips -= 1;
+                    }
+                    if let Some(pl) = &mut self.players_by_seats[*winner_seat] {
+                        pl.collect_win(winnings);
+                    }
+                    self.ledger.record(
+                        self.hand_number,
+                        *winner_seat as u8,
+                        LedgerEntryKind::Win,
+                        winnings,
+                    );
+                }
+            }
+            GameEvent::HandEnded { summary, showdown } => {
+                if let Some(mut history) = self.current_hand.take() {
+                    history.boards = summary.boards.clone();
+                    history.pots = summary.pots.clone();
+                    history.showdown = showdown.clone();
+                    self.hand_histories.push(history);
+                }
+                self.last_hand = Some(summary.clone());
+                // the pot is gone, so are the bets that built it
+                self.reset_players();
+                self.game_phase = GamePhase::PreFlop;
+                self.community_cards_shown = 0;
+            }
+            GameEvent::ChipAlertRaised { alert } => self.ledger.raise_alert(*alert),
         }
     }
 
@@ -299,7 +432,10 @@ impl Game {
         seat_index: u8,
         nickname: &str,
         appearance_type: u8,
-    ) -> Result<Uuid, &str> {
+    ) -> Result<Uuid, &'static str> {
+        if seat_index as usize >= self.max_players {
+            return Err("no such seat");
+        }
         match self.players_by_seats[seat_index as usize] {
             Some(_) => return Err("seat already taken"),
             _ => (),
@@ -308,11 +444,13 @@ impl Game {
             return Err("game already started or ended");
         }
         let player_id = Uuid::new_v4();
-        let player = Player::new_player(seat_index, self.initial_balance, appearance_type);
-        self.players.insert(player_id, seat_index as usize);
-        self.players_by_seats[seat_index as usize] = Some(player);
-
-        self.nicknames[seat_index as usize] = Some(nickname.to_string());
+        self.emit(GameEvent::PlayerJoined {
+            player_id,
+            seat_index,
+            nickname: nickname.to_string(),
+            appearance_type,
+            balance: self.initial_balance,
+        });
 
         if self.players.len() == self.max_players {
             let _ = self.start_game();
@@ -321,25 +459,173 @@ impl Game {
         Ok(player_id)
     }
 
-    pub fn set_ready(&mut self, player_index: usize, ready: bool) -> Result<bool, &str> {
+    // Seats a computer player, always ready. Only while the game waits to start.
+    pub fn add_bot(&mut self, seat_index: Option<u8>, kind: BotKind) -> Result<u8, &'static str> {
+        if self.game_state != GamePlayState::NotStarted {
+            return Err("game already started or ended");
+        }
+        let seat_index = match seat_index {
+            Some(seat_index) if seat_index as usize >= self.max_players => {
+                return Err("no such seat")
+            }
+            Some(seat_index) if self.players_by_seats[seat_index as usize].is_some() => {
+                return Err("seat already taken")
+            }
+            Some(seat_index) => seat_index,
+            None => match self.players_by_seats.iter().position(|seat| seat.is_none()) {
+                None => return Err("no free seat"),
+                Some(seat_index) => seat_index as u8,
+            },
+        };
+        self.emit(GameEvent::PlayerJoined {
+            player_id: Uuid::new_v4(),
+            seat_index,
+            nickname: format!("{:?} bot {}", kind, seat_index + 1),
+            appearance_type: 0,
+            balance: self.initial_balance,
+        });
+        self.emit(GameEvent::BotSeated { seat_index, kind });
+        self.emit(GameEvent::ReadyChanged {
+            seat_index,
+            ready: true,
+        });
+        // bots alone do not start a game unless they fill the table
+        if self.players.len() == self.max_players {
+            let _ = self.start_game();
+        }
+
+        Ok(seat_index)
+    }
+
+    pub fn evaluator(&self) -> Arc<Evaluator> {
+        self.evaluator.clone()
+    }
+
+    // seats taken by computer players
+    pub fn bot_seats(&self) -> Vec<(usize, BotKind)> {
+        self.bots
+            .iter()
+            .enumerate()
+            .filter_map(|(seat, kind)| kind.map(|kind| (seat, kind)))
+            .collect()
+    }
+
+    pub fn set_ready(&mut self, player_index: usize, ready: bool) -> Result<bool, &'static str> {
         if self.game_state != GamePlayState::NotStarted {
             return Err("game already started or ended");
         }
 
-        let player: &mut Player = self.players_by_seats[player_index].as_mut().unwrap();
-        player.set_ready(ready);
+        self.emit(GameEvent::ReadyChanged {
+            seat_index: player_index as u8,
+            ready,
+        });
         let _ = self.start_game();
 
         Ok(ready)
     }
 
+    pub fn buy_chips(
+        &mut self,
+        player_index: usize,
+        kind: BuyInKind,
+        amount: u64,
+    ) -> Result<u64, &'static str> {
+        if self.game_state == GamePlayState::Ended {
+            return Err("game already ended");
+        }
+        let player = match self.players_by_seats[player_index] {
+            Some(player) => player,
+            None => return Err("seat empty"),
+        };
+        if player.state == PlayerState::Left {
+            return Err("player left the game");
+        }
+
+        let pending: u64 = self
+            .pending_buy_ins
+            .iter()
+            .filter(|pending| pending.seat_index == player.seat_index)
+            .map(|pending| pending.amount)
+            .sum();
+        let player_id = self.player_at(player.seat_index as usize);
+        let add_ons_taken = self.ledger.count(player_id, LedgerEntryKind::AddOn)
+            + self
+                .pending_buy_ins
+                .iter()
+                .filter(|pending| {
+                    pending.seat_index == player.seat_index
+                        && pending.kind == LedgerEntryKind::AddOn
+                })
+                .count();
+
+        let chips = self.buy_in_model.chips_for(
+            kind,
+            amount,
+            player.balance + pending,
+            self.initial_balance,
+            self.hand_number,
+            add_ons_taken,
+        )?;
+
+        let buy_in = PendingBuyIn {
+            seat_index: player.seat_index,
+            kind: kind.to_ledger(),
+            amount: chips,
+        };
+        if !self.hand_in_progress() {
+            // before the game or between hands, a busted player is back in for the next hand
+            self.emit(GameEvent::ChipsBought { buy_in });
+            self.check_chip_conservation();
+        } else {
+            debug!(
+                "player at seat {} queued {:?} of {} for the next hand",
+                player.seat_index, buy_in.kind, chips
+            );
+            self.emit(GameEvent::BuyInQueued { buy_in });
+        }
+
+        Ok(chips)
+    }
+
+    pub fn cash_out(&mut self, player_id: Uuid) -> Result<u64, &'static str> {
+        if self.game_state == GamePlayState::Started {
+            return Err("cannot cash out while the game is running");
+        }
+        let player = match self.players.get(&player_id) {
+            Some(seat_index) => self.players_by_seats[*seat_index].unwrap(),
+            None => return Err("player not found"),
+        };
+        self.emit(GameEvent::CashedOut {
+            player_id,
+            seat_index: player.seat_index,
+        });
+        debug!(
+            "player at seat {} cashed out {}",
+            player.seat_index, player.balance
+        );
+        self.check_chip_conservation();
+
+        Ok(player.balance)
+    }
+
+    pub fn ledger(&self) -> &ChipLedger {
+        &self.ledger
+    }
+
     pub fn player_action(&mut self, player_index: usize, action: PlayerAction, amount: u64) -> u8 {
-        if player_index != self.active_player {
+        if action == PlayerAction::RunIt {
+            // any involved player may choose, regardless of whose turn it is
+            return match self.choose_runouts(player_index, amount) {
+                Err(_) => 0,
+                Ok(_) => 1,
+            };
+        }
+        if player_index != self.active_player || self.runout_vote.is_some() {
             return 0;
         }
 
         let max_bet = self.max_bet();
-        let player: &mut Player = self.players_by_seats[player_index].as_mut().unwrap();
+        let player: Player = self.players_by_seats[player_index].unwrap();
 
         if (amount + player.current_bet < max_bet && action == PlayerAction::Bet)
             || (player.state == PlayerState::AllIn && action != PlayerAction::AllIn)
@@ -347,10 +633,16 @@ impl Game {
             return 0;
         }
 
+        // tried on a copy, the event applies it to the player at the table
+        let mut after_action = player;
         let result = match action {
-            PlayerAction::Call | PlayerAction::Check => player.perform_action(action, max_bet),
-            PlayerAction::Bet => player.perform_action(action, amount),
-            PlayerAction::AllIn | PlayerAction::Fold => player.perform_action(action, amount),
+            PlayerAction::Call | PlayerAction::Check => {
+                after_action.perform_action(action, max_bet)
+            }
+            PlayerAction::Bet => after_action.perform_action(action, amount),
+            PlayerAction::AllIn | PlayerAction::Fold | PlayerAction::RunIt => {
+                after_action.perform_action(action, amount)
+            }
         };
 
         match result {
@@ -358,49 +650,53 @@ impl Game {
             Ok(_) => (),
         }
 
-        if !self.round_end() {
-            println!("round not ended");
-            self.set_next_active_player();
+        self.emit(GameEvent::ActionTaken {
+            seat_index: player_index as u8,
+            action,
+            amount: player.balance - after_action.balance,
+        });
+
+        if self.unfolded_count() == 1 {
+            debug!("everyone else folded");
+            self.award_uncontested();
+        } else if self.betting_closed() {
+            debug!("no further betting possible, running out the board");
+            self.close_betting();
+        } else if !self.round_end() {
+            debug!("round not ended");
+            self.emit(GameEvent::TurnPassed {
+                seat_index: self.get_next_active_player(),
+            });
         } else {
-            self.active_player = self.dealer_seat;
-            self.set_next_active_player();
             match self.game_phase {
-                GamePhase::PreFlop => {
-                    self.game_phase = GamePhase::Flop;
-                    self.set_players_active(false);
-                    self.community_cards_shown = 3;
-                    self.collect_bets();
-                }
-                GamePhase::Flop => {
-                    self.game_phase = GamePhase::Turn;
-                    self.set_players_active(false);
-                    self.community_cards_shown = 4;
-                    self.collect_bets();
-                }
-                GamePhase::Turn => {
-                    self.game_phase = GamePhase::River;
-                    self.set_players_active(false);
-                    self.community_cards_shown = 5;
-                    self.collect_bets();
-                }
-                GamePhase::River => {
-                    self.collect_bets();
-                    let winner_seat = self.get_winner_seat();
-                    self.distribute_winnings(winner_seat);
-                    self.game_phase = GamePhase::PreFlop;
-                    self.community_cards_shown = 0;
-                    self.start_round(false);
-                }
+                GamePhase::PreFlop => self.deal_street(GamePhase::Flop),
+                GamePhase::Flop => self.deal_street(GamePhase::Turn),
+                GamePhase::Turn => self.deal_street(GamePhase::River),
+                GamePhase::River => self.showdown(),
             }
         }
 
-        println!("current round phase is {:?}", self.game_phase);
+        debug!("current round phase is {:?}", self.game_phase);
+
+        self.check_chip_conservation();
 
         1
 
         // Ok(result)
     }
 
+    fn deal_street(&mut self, street: GamePhase) {
+        self.collect_bets();
+        let cards = match street {
+            GamePhase::PreFlop => &self.community_cards[..0],
+            GamePhase::Flop => &self.community_cards[..3],
+            GamePhase::Turn => &self.community_cards[3..4],
+            GamePhase::River => &self.community_cards[4..],
+        };
+        let cards = cards.iter().flatten().copied().collect();
+        self.emit(GameEvent::StreetDealt { street, cards });
+    }
+
     pub fn collect_state_data(&self, player_id: Uuid) -> GameState {
         let player_seat = self.players.get(&player_id);
         let mut cards_to_show = self.community_cards;
@@ -412,6 +708,26 @@ impl Game {
             asker_seat: player_seat.copied(),
             active_seat: self.active_player,
             community_cards: cards_to_show,
+            boards: {
+                let mut boards = vec![cards_to_show];
+                boards.extend(self.extra_boards.iter().copied());
+                boards
+            },
+            runout_vote: self.runout_vote.clone(),
+            tabled_hands: self.tabled_hands.clone(),
+            hints: match player_seat {
+                Some(seat) if self.training => self.hints_for(*seat, &cards_to_show),
+                _ => None,
+            },
+            hand_commitment: match (&self.shuffle_proof, self.game_state) {
+                (Some(proof), GamePlayState::Started) => Some(proof.commitment.clone()),
+                _ => None,
+            },
+            next_hand_commitment: match self.deck_source {
+                DeckSource::ProvablyFair => Some(self.server_seed.commitment()),
+                _ => None,
+            },
+            last_hand: self.last_hand.clone(),
             personal_cards: match player_seat {
                 Some(player_index) if self.game_state == GamePlayState::Started => self
                     .players_by_seats[*player_index]
@@ -431,10 +747,16 @@ impl Game {
                 .sum(),
             small_blind: self.small_blind,
             big_blind: self.big_blind,
+            blind_level: self.blind_level,
+            next_level_at: self.next_level_at(),
+            next_hand_at: self.next_hand_at(),
             game_state: self.game_state,
+            paused: self.paused,
+            hand_number: self.hand_number,
             dealer_seat: self.dealer_seat,
             small_blind_seat: self.small_blind_seat,
             big_blind_seat: self.big_blind_seat,
+            hud: Vec::new(),
             players: self
                 .players_by_seats
                 .iter()
@@ -448,6 +770,7 @@ impl Game {
                             .as_ref()
                             .unwrap()
                             .to_string(),
+                        bot: self.bots[player.seat_index as usize],
                     }),
                     None => None,
                 })
@@ -455,6 +778,33 @@ impl Game {
         }
     }
 
+    // only from the player's own cards and the board shown to everybody
+    fn hints_for(&self, seat: usize, board: &[Option<Card>; 5]) -> Option<TrainingHints> {
+        let player = self.players_by_seats[seat].as_ref()?;
+        let in_hand = matches!(
+            player.state,
+            PlayerState::Active | PlayerState::Check | PlayerState::Call | PlayerState::AllIn
+        );
+        if self.game_state != GamePlayState::Started || !in_hand {
+            return None;
+        }
+        let board: Vec<Card> = board.iter().flatten().copied().collect();
+        let to_call = (self.max_bet() - player.current_bet).min(player.balance);
+        let pot = self
+            .players_by_seats
+            .iter()
+            .flatten()
+            .map(|player| player.current_bet + player.total_bet)
+            .sum();
+        Some(training_hints(
+            &self.evaluator,
+            &player.cards,
+            &board,
+            to_call,
+            pot,
+        ))
+    }
+
     pub fn start_game(&mut self) -> Result<u64, &str> {
         if self.players_count() < 3 {
             return Err("too few players");
@@ -470,9 +820,9 @@ impl Game {
             }
         }
 
-        println!("game running, poker module version 1.0.0");
+        info!("game running, poker module version 1.0.0");
 
-        self.game_state = GamePlayState::Started;
+        self.emit(GameEvent::GameStarted);
 
         self.start_round(true);
 
@@ -480,132 +830,550 @@ impl Game {
     }
 
     pub fn start_round(&mut self, first_round: bool) {
-        if self.someone_won() {
-            self.game_state = GamePlayState::Ended;
+        for buy_in in self.pending_buy_ins.clone() {
+            self.emit(GameEvent::ChipsBought { buy_in });
+            self.check_chip_conservation();
+        }
+
+        let final_hand_played = match self.final_hand {
+            Some(final_hand) => self.hand_number >= final_hand,
+            None => false,
+        };
+        if self.someone_won() || final_hand_played {
+            self.emit(GameEvent::GameEnded);
             // self.purge_players();
             return;
         }
 
-        self.deal_cards();
-        if first_round {
-            self.dealer_seat = self.first_taken_seat();
-        } else {
-            self.dealer_seat =
-                next_player(&self.players_by_seats, self.dealer_seat, self.max_players);
+        if self.paused {
+            debug!("game paused, the next hand waits for it to resume");
+            return;
         }
-        self.active_player = self.dealer_seat;
-        self.set_players_active(true);
-        self.reset_players();
-        self.set_next_active_player();
 
-        self.small_blind_seat = self.active_player;
+        let dealer_seat = match first_round {
+            true => self.first_taken_seat(),
+            false => next_player(&self.players_by_seats, self.dealer_seat, self.max_players),
+        };
+        self.begin_hand(self.hand_number + 1, dealer_seat);
+    }
 
-        let message = self.player_action(self.active_player, PlayerAction::Bet, self.small_blind);
-        match message {
-            0 => {
-                let _ = self.player_action(self.active_player, PlayerAction::AllIn, 0);
-            }
-            _ => (),
+    // Stops dealing new hands, the hand in progress can still be played to the end.
+    pub fn pause(&mut self) {
+        if self.paused || self.game_state == GamePlayState::Ended {
+            return;
+        }
+        self.emit(GameEvent::GamePaused);
+    }
+
+    pub fn resume(&mut self) {
+        if !self.paused {
+            return;
         }
+        self.emit(GameEvent::GameResumed);
+        // a scheduled hand is dealt by the table once its pause is over
+        if self.game_state == GamePlayState::Started
+            && !self.hand_in_progress()
+            && self.next_hand_at.is_none()
+        {
+            self.start_round(self.hand_number == 0);
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn hand_in_progress(&self) -> bool {
+        self.current_hand.is_some()
+    }
+
+    pub fn hand_number(&self) -> u32 {
+        self.hand_number
+    }
 
-        self.big_blind_seat = self.active_player;
+    // seat whose turn it is, meaningless while no hand is in progress
+    pub fn active_seat(&self) -> usize {
+        self.active_player
+    }
 
-        let message = self.player_action(self.active_player, PlayerAction::Bet, self.big_blind);
-        match message {
-            0 => {
-                let _ = self.player_action(self.active_player, PlayerAction::AllIn, 0);
+    pub fn runout_vote(&self) -> Option<&RunoutVote> {
+        self.runout_vote.as_ref()
+    }
+
+    fn begin_hand(&mut self, hand_number: u32, dealer_seat: usize) {
+        let (deck, shuffle_proof, next_server_seed) = match self.shuffle(hand_number) {
+            Ok(shuffled) => shuffled,
+            Err(err) => {
+                // nothing is dealt, resuming the game tries the next deck
+                error!("hand {} not dealt: {}", hand_number, err);
+                self.emit(GameEvent::GamePaused);
+                return;
             }
-            _ => (),
+        };
+        self.emit(GameEvent::HandStarted {
+            hand_number,
+            dealer_seat,
+            deck: deck.to_vec(),
+            shuffle_proof,
+            next_server_seed,
+            started_at: unix_now(),
+        });
+
+        self.post_blind(self.small_blind, HandAction::SmallBlind);
+        self.post_blind(self.big_blind, HandAction::BigBlind);
+
+        if self.betting_closed() {
+            debug!("blinds put everyone all in, running out the board");
+            self.close_betting();
         }
     }
 
-    pub fn set_next_active_player(&mut self) {
-        let mut next_player_seat =
-            next_player(&self.players_by_seats, self.active_player, self.max_players);
-        let mut next_player_active: bool = match self.players_by_seats[next_player_seat]
-            .as_ref()
-            .unwrap()
-            .state
-        {
-            PlayerState::Folded | PlayerState::Left => false,
-            _ => true,
+    fn post_blind(&mut self, amount: u64, blind: HandAction) {
+        let player: Player = self.players_by_seats[self.active_player].unwrap();
+        self.emit(GameEvent::BlindPosted {
+            seat_index: player.seat_index,
+            blind,
+            // short stacks go all in
+            amount: amount.min(player.balance),
+        });
+    }
+
+    fn begin_hand_history(&mut self, started_at: u64) {
+        // called once the cards are dealt and the dealer is known, before the blinds
+        let seats = self
+            .players_by_seats
+            .iter()
+            .flatten()
+            .map(|pl| SeatRecord {
+                seat_index: pl.seat_index,
+                nickname: self.nicknames[pl.seat_index as usize]
+                    .clone()
+                    .unwrap_or_default(),
+                player_id: self.player_at(pl.seat_index as usize),
+                stack: pl.balance,
+                cards: match pl.state {
+                    PlayerState::Folded | PlayerState::Left => None,
+                    _ => Some(pl.cards),
+                },
+            })
+            .collect();
+        self.current_hand = Some(HandHistory {
+            hand_number: self.hand_number,
+            started_at,
+            table: TableRecord {
+                max_players: self.max_players,
+                small_blind: self.small_blind,
+                big_blind: self.big_blind,
+                initial_balance: self.initial_balance,
+                buy_in_model: self.buy_in_model,
+                max_boards: self.max_boards,
+                rabbit_hunt: self.rabbit_hunt,
+                action_clock: self.action_clock,
+                training: self.training,
+                hand_pause: self.hand_pause,
+                level_duration: self.level_duration,
+            },
+            dealer_seat: self.dealer_seat,
+            small_blind_seat: self.small_blind_seat,
+            big_blind_seat: self.big_blind_seat,
+            seats,
+            actions: Vec::new(),
+            boards: Vec::new(),
+            showdown: Vec::new(),
+            pots: Vec::new(),
+            deck: Some(self.deck.to_vec()),
+        });
+    }
+
+    fn record_hand_action(&mut self, seat: usize, action: HandAction, amount: u64) {
+        let balance = match self.players_by_seats[seat] {
+            Some(pl) => pl.balance,
+            None => 0,
         };
-        let mut i = 0;
-        while !next_player_active {
-            next_player_seat = next_player(
-                &self.players_by_seats,
-                (self.active_player + i) % self.max_players,
-                self.max_players,
+        if let Some(history) = &mut self.current_hand {
+            history.record_action(self.game_phase, seat as u8, action, amount, balance);
+        }
+    }
+
+    pub fn hand_histories(&self) -> &[HandHistory] {
+        &self.hand_histories
+    }
+
+    pub fn hand_history(&self, hand_number: u32) -> Option<&HandHistory> {
+        self.hand_histories
+            .iter()
+            .find(|history| history.hand_number == hand_number)
+    }
+
+    pub fn set_next_active_player(&mut self) {
+        self.active_player = self.get_next_active_player();
+        debug!("next active player {}", self.active_player);
+    }
+
+    fn set_players_active(&mut self, force: bool) {
+        for seat in self.players.values() {
+            debug!(
+                "player state {:?}",
+                self.players_by_seats[*seat].unwrap().state
             );
-            next_player_active = match self.players_by_seats[next_player_seat]
-                .as_ref()
-                .unwrap()
-                .state
-            {
-                PlayerState::Folded | PlayerState::Left => false,
-                _ => true,
+            let player = self.players_by_seats[*seat].unwrap();
+            self.players_by_seats[*seat] = Some(player.set_active(force));
+            debug!(
+                "player {} state {:?}",
+                player.seat_index,
+                self.players_by_seats[*seat].unwrap().state
+            );
+            // if let Some(mut player) = self.players_by_seats[seat] {
+            //     println!("setting player {} as active", player.seat_index);
+            //     let _ = player.set_active(force);
+            //     println!("player state {:?}", player.state);
+            // }
+
+            // if let Some(player) = self.players_by_seats[*seat] {println!("player state2 {:?}", player.state);}
+        }
+    }
+
+    fn collect_bets(&mut self) {
+        self.return_uncalled_bet();
+        self.emit(GameEvent::BetsCollected);
+    }
+
+    fn return_uncalled_bet(&mut self) {
+        // the part of the highest bet nobody matched goes back to the bettor instead of the pot
+        let mut highest_seat = 0;
+        let mut highest_bet = 0;
+        let mut second_bet = 0;
+        for seat in 0..self.max_players {
+            match self.players_by_seats[seat] {
+                Some(pl) if pl.current_bet > highest_bet => {
+                    second_bet = highest_bet;
+                    highest_bet = pl.current_bet;
+                    highest_seat = seat;
+                }
+                Some(pl) if pl.current_bet > second_bet => second_bet = pl.current_bet,
+                _ => (),
             };
-            i = i + 1;
-            if i > 10 {
-                println!("cant find active player!");
+        }
+
+        let uncalled = highest_bet - second_bet;
+        if uncalled == 0 {
+            return;
+        }
+        self.emit(GameEvent::BetReturned {
+            seat_index: highest_seat as u8,
+            amount: uncalled,
+        });
+    }
+
+    fn award_uncontested(&mut self) {
+        self.collect_bets();
+        self.distribute_winnings();
+        self.end_hand();
+    }
+
+    // deals the next hand right away, or has the table wait for hand_pause seconds first
+    fn end_hand(&mut self) {
+        match self.hand_pause {
+            0 => self.start_round(false),
+            pause => self.emit(GameEvent::NextHandScheduled {
+                at: unix_now() + pause,
+            }),
+        }
+    }
+
+    // The pause between hands is over, called by the table. Returns whether a hand was due.
+    pub fn deal_next_hand(&mut self) -> bool {
+        if self.next_hand_at.is_none() || self.hand_in_progress() {
+            return false;
+        }
+        self.start_round(false);
+        true
+    }
+
+    // unix time the next hand is dealt at, None while a hand is played or without a pause
+    pub fn next_hand_at(&self) -> Option<u64> {
+        match self.game_state {
+            GamePlayState::Started => self.next_hand_at,
+            _ => None,
+        }
+    }
+
+    // unix time the blinds double at, None with fixed blinds or before the first hand
+    pub fn next_level_at(&self) -> Option<u64> {
+        match (self.level_duration, self.game_state, self.level_started_at) {
+            (0, _, _) => None,
+            (duration, GamePlayState::Started, Some(started_at)) => Some(started_at + duration),
+            _ => None,
+        }
+    }
+
+    // Doubles the blinds from the next hand on, called by the table when the level is over.
+    pub fn raise_blinds(&mut self) {
+        if self.next_level_at().is_none() {
+            return;
+        }
+        self.emit(GameEvent::BlindsRaised {
+            small_blind: self.small_blind * 2,
+            big_blind: self.big_blind * 2,
+            raised_at: unix_now(),
+        });
+    }
+
+    // The player to act ran out of time: checks when possible, folds otherwise. Players yet to
+    // choose how many times to run it get a single board. Returns whether anything happened.
+    pub fn time_out(&mut self) -> bool {
+        if !self.hand_in_progress() {
+            return false;
+        }
+        if let Some(vote) = self.runout_vote.clone() {
+            for choice in vote.choices.iter().filter(|choice| choice.boards.is_none()) {
+                debug!("seat {} ran out of time to choose", choice.seat_index);
+                let _ = self.choose_runouts(choice.seat_index, 1);
+            }
+            return true;
+        }
+        let seat = self.active_player;
+        debug!("seat {} ran out of time", seat);
+        self.player_action(seat, PlayerAction::Check, 0) != 0
+            || self.player_action(seat, PlayerAction::Fold, 0) != 0
+    }
+
+    pub fn choose_runouts(&mut self, player_index: usize, boards: u64) -> Result<u8, &str> {
+        let mut vote = match &self.runout_vote {
+            None => return Err("no runout to choose"),
+            Some(vote) => vote.clone(),
+        };
+        if boards > self.max_boards as u64 {
+            return Err("unsupported number of boards");
+        }
+        vote.choose(player_index, boards as u8)?;
+        debug!("player at seat {} wants {} boards", player_index, boards);
+        self.emit(GameEvent::RunoutChosen {
+            seat_index: player_index,
+            boards: boards as u8,
+        });
+
+        if let Some(decided) = vote.decided() {
+            debug!("running the board {} times", decided);
+            self.emit(GameEvent::BoardsRun { boards: decided });
+            self.showdown();
+            self.check_chip_conservation();
+        }
+
+        Ok(boards as u8)
+    }
+
+    fn close_betting(&mut self) {
+        self.table_hands();
+        if self.max_boards > 1 && self.community_cards_shown < 5 && self.unfolded_count() > 1 {
+            // settle the bets now so stacks are final while players choose
+            self.collect_bets();
+            let seats: Vec<usize> = self
+                .players_by_seats
+                .iter()
+                .flatten()
+                .filter(|pl| !matches!(pl.state, PlayerState::Folded | PlayerState::Left))
+                .map(|pl| pl.seat_index as usize)
+                .collect();
+            debug!("seats {:?} choose how many times to run it", seats);
+            self.emit(GameEvent::RunoutVoteOpened { seats });
+            return;
+        }
+        self.showdown();
+    }
+
+    // Players still in show their cards when no more bets can be made with cards to come,
+    // everyone gets to see their chances before the rest of the board is dealt
+    fn table_hands(&mut self) {
+        let players: Vec<Player> = self
+            .players_by_seats
+            .iter()
+            .flatten()
+            .filter(|pl| !matches!(pl.state, PlayerState::Folded | PlayerState::Left))
+            .copied()
+            .collect();
+        if players.len() < 2 || self.community_cards_shown >= 5 {
+            return;
+        }
+        let holdings: Vec<Holding> = players.iter().map(|pl| Holding::Cards(pl.cards)).collect();
+        let board: Vec<Card> = self.community_cards[..self.community_cards_shown]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        // runouts drawn from the deck's own seed, the same deck always shows the same equities
+        let mut hasher = Sha256::new();
+        for card in &self.deck {
+            hasher.update(card.to_string());
+        }
+        let mut rng = StdRng::from_seed(hasher.finalize().into());
+        let result = match equity(&self.evaluator, &holdings, &board, &[], &mut rng) {
+            Err(err) => {
+                warn!(
+                    "could not compute the equities of the tabled hands: {}",
+                    err
+                );
                 return;
             }
+            Ok(result) => result,
+        };
+        let hands = players
+            .iter()
+            .zip(result.players)
+            .map(|(pl, equity)| TabledHand {
+                seat_index: pl.seat_index,
+                cards: pl.cards,
+                equity,
+            })
+            .collect();
+        self.emit(GameEvent::HandsTabled { hands });
+    }
+
+    fn deal_extra_boards(&mut self, boards: u8) {
+        // cards already on the table are shared, every board gets its own rest of the runout
+        for _ in 1..boards {
+            let mut board = self.community_cards;
+            for card in board.iter_mut().skip(self.community_cards_shown) {
+                *card = Some(self.deck[self.next_deck_card]);
+                self.next_deck_card += 1;
+            }
+            self.extra_boards.push(board);
         }
-        println!("next active player {}", next_player_seat);
-        self.active_player = next_player_seat;
-        // if self.players_by_seats[self.active_player].unwrap().state == PlayerState::AllIn {
-        //     self.player_action(self.active_player, PlayerAction::AllIn, 0);
-        // }
     }
 
-    fn set_players_active(&mut self, force: bool) {
-        for seat in self.players.values() {
-            println!(
-                "player state {:?}",
-                self.players_by_seats[*seat].unwrap().state
-            );
-            let player = self.players_by_seats[*seat].unwrap();
-            self.players_by_seats[*seat] = Some(player.set_active(force));
-            println!(
-                "player {} state {:?}",
-                player.seat_index,
-                self.players_by_seats[*seat].unwrap().state
-            );
-            // if let Some(mut player) = self.players_by_seats[seat] {
-            //     println!("setting player {} as active", player.seat_index);
-            //     let _ = player.set_active(force);
-            //     println!("player state {:?}", player.state);
-            // }
+    fn boards(&self) -> Vec<[Option<Card>; 5]> {
+        let mut boards = vec![self.community_cards];
+        boards.extend(self.extra_boards.iter().copied());
+        boards
+    }
 
-            // if let Some(player) = self.players_by_seats[*seat] {println!("player state2 {:?}", player.state);}
-        }
+    fn showdown(&mut self) {
+        // also used when all-ins end the betting early, the rest of the board is dealt out
+        self.collect_bets();
+        self.emit(GameEvent::BoardCompleted);
+        self.distribute_winnings();
+        self.end_hand();
     }
 
-    fn collect_bets(&mut self) {
-        for seat in 0..self.max_players {
-            let _ = match &mut self.players_by_seats[seat] {
-                Some(pl) => pl.collect_bet(),
-                _ => Err("seat empty"),
-            };
+    fn distribute_winnings(&mut self) {
+        let contributions: Vec<Contribution> = self
+            .players_by_seats
+            .iter()
+            .flatten()
+            .map(|player| Contribution {
+                seat_index: player.seat_index as usize,
+                amount: player.current_bet + player.total_bet,
+                contesting: !matches!(player.state, PlayerState::Folded | PlayerState::Left),
+            })
+            .collect();
+
+        let boards = self.boards();
+        let mut shown_boards = boards.clone();
+        for board in shown_boards.iter_mut() {
+            board[self.community_cards_shown..].fill(None);
+        }
+        let rabbit_cards = match self.rabbit_hunt {
+            true => self.community_cards[self.community_cards_shown..]
+                .iter()
+                .flatten()
+                .copied()
+                .collect(),
+            false => Vec::new(),
+        };
+        let mut summary = HandSummary {
+            hand_number: self.hand_number,
+            boards: shown_boards,
+            rabbit_cards,
+            pots: Vec::new(),
+            shuffle_proof: self.shuffle_proof.clone(),
+            tabled_hands: self.tabled_hands.clone(),
+        };
+
+        for pot in build_pots(&contributions) {
+            // every board plays for an equal part of each pot, odd chips go to the first board
+            let board_share = pot.amount / boards.len() as u64;
+            let mut board_odd_chips = pot.amount % boards.len() as u64;
+            for (board_index, board) in boards.iter().enumerate() {
+                let mut board_amount = board_share;
+                if board_odd_chips > 0 {
+                    board_amount += 1;
+                    board_odd_chips -= 1;
+                }
+
+                let winners = self.winning_seats(board, &pot.eligible_seats);
+                if winners.is_empty() {
+                    warn!("no winner found for pot of {}", board_amount);
+                    continue;
+                }
+                debug!(
+                    "pot of {} on board {} goes to seats {:?}, eligible were {:?}",
+                    board_amount, board_index, winners, pot.eligible_seats
+                );
+                // split evenly, odd chips go to the first winners
+                summary.pots.push(PotResult {
+                    amount: board_amount,
+                    board: board_index,
+                    winners,
+                    eligible_seats: pot.eligible_seats.clone(),
+                });
+            }
         }
+        let showdown = self.showdown_records(&boards, &contributions);
+        for pot in &summary.pots {
+            self.emit(GameEvent::PotAwarded { pot: pot.clone() });
+        }
+        self.emit(GameEvent::HandEnded { summary, showdown });
     }
 
-    fn distribute_winnings(&mut self, winner_seat: usize) {
-        let winnings: u64 = self
-            .players_by_seats
+    fn table_chips(&self) -> u64 {
+        self.players_by_seats
             .iter()
             .map(|opt_player| match opt_player {
-                Some(player) => player.current_bet + player.total_bet,
+                Some(player) => player.balance + player.current_bet + player.total_bet,
                 None => 0,
             })
-            .sum();
-        println!("winnings are {}, win seat is {}", winnings, winner_seat);
+            .sum()
+    }
+
+    fn check_chip_conservation(&mut self) {
+        let expected = self.ledger.expected_table_chips();
+        let actual = self.table_chips();
+        if expected == actual {
+            return;
+        }
+        self.emit(GameEvent::ChipAlertRaised {
+            alert: ChipAlert {
+                hand_number: self.hand_number,
+                expected,
+                actual,
+            },
+        });
+        debug_assert!(
+            false,
+            "chip conservation violated: expected {} chips on the table, found {}",
+            expected, actual
+        );
+    }
+
+    fn apply_buy_in(&mut self, buy_in: PendingBuyIn) {
+        match &mut self.players_by_seats[buy_in.seat_index as usize] {
+            Some(pl) => pl.add_chips(buy_in.amount),
+            None => return,
+        }
+        self.ledger.record(
+            self.hand_number,
+            buy_in.seat_index,
+            buy_in.kind,
+            buy_in.amount,
+        );
+    }
+
+    fn sit_out_busted_players(&mut self) {
+        // players without chips stay at the table (they may still rebuy) but are not dealt in
         for seat in 0..self.max_players {
             match &mut self.players_by_seats[seat] {
-                Some(pl) => {
-                    if pl.seat_index == winner_seat as u8 {
-                        pl.collect_win(winnings)
-                    }
+                Some(pl) if pl.balance == 0 && pl.state != PlayerState::Left => {
+                    let _ = pl.perform_action(PlayerAction::Fold, 0);
                 }
                 _ => (),
             };
@@ -614,20 +1382,20 @@ impl Game {
 
     fn someone_won(&self) -> bool {
         let mut non_zero_balance: u8 = 0;
-        println!("checking if someone won");
+        debug!("checking if someone won");
         for seat in 0..self.max_players {
-            println!("checking if someone won, seat {}", seat);
+            debug!("checking if someone won, seat {}", seat);
             match self.players_by_seats[seat] {
                 Some(pl) => {
                     if pl.balance > 0 {
                         non_zero_balance += 1;
-                        println!("found {} non-zero balance", pl.seat_index);
+                        debug!("found {} non-zero balance", pl.seat_index);
                     }
                 }
                 _ => (),
             };
         }
-        println!("checking if someone won, status {}", non_zero_balance <= 2);
+        debug!("checking if someone won, status {}", non_zero_balance <= 2);
 
         non_zero_balance <= 2
     }
@@ -666,7 +1434,7 @@ impl Game {
             .unwrap()
             .state
         {
-            PlayerState::Folded | PlayerState::Left => false,
+            PlayerState::Folded | PlayerState::Left | PlayerState::AllIn => false,
             _ => true,
         };
         let mut i = 0;
@@ -681,20 +1449,19 @@ impl Game {
                 .unwrap()
                 .state
             {
-                PlayerState::Folded | PlayerState::Left => false,
+                PlayerState::Folded | PlayerState::Left | PlayerState::AllIn => false,
                 _ => true,
             };
             i = i + 1;
             if i > 10 {
-                println!("cant find active player!");
-                return 0;
+                warn!("cant find active player!");
+                return self.active_player;
             }
         }
         next_player_seat
     }
 
     fn deal_cards(&mut self) {
-        self.shuffle();
         let mut next_card = 0;
         for player in self.players_by_seats.iter_mut() {
             match player {
@@ -709,58 +1476,181 @@ impl Game {
         for card_offset in 0..5 {
             self.community_cards[card_offset] = Some(self.deck[next_card + card_offset].clone());
         }
+        self.next_deck_card = next_card + 5;
+        self.extra_boards.clear();
+    }
+
+    pub fn with_deck_source(mut self, deck_source: DeckSource) -> Self {
+        self.deck_source = deck_source;
+        self
+    }
+
+    // Fresh game seated like the table at the start of a recorded hand, dealing its recorded
+    // deck. It plays that hand only, the blinds are posted right away. Building an evaluator
+    // takes a while, replays share one.
+    pub fn replay_of(
+        history: &HandHistory,
+        evaluator: Arc<Evaluator>,
+    ) -> Result<Game, &'static str> {
+        let deck = match &history.deck {
+            None => return Err("hand has no recorded deck"),
+            Some(deck) => deck.clone(),
+        };
+        let table = TableRecord {
+            // the replay ends with its hand, without a pause or blind levels
+            hand_pause: 0,
+            level_duration: 0,
+            ..history.table
+        };
+        let mut game = Game::created(table, ServerSeed::new_seed(), evaluator)
+            .with_deck_source(DeckSource::stacked(vec![deck])?);
+
+        game.final_hand = Some(history.hand_number);
+
+        for seat in &history.seats {
+            game.emit(GameEvent::PlayerJoined {
+                player_id: Uuid::new_v4(),
+                seat_index: seat.seat_index,
+                nickname: seat.nickname.clone(),
+                appearance_type: 0,
+                balance: seat.stack,
+            });
+        }
+        game.emit(GameEvent::GameStarted);
+        game.begin_hand(history.hand_number, history.dealer_seat);
+        Ok(game)
+    }
+
+    // deck of the next hand, from the deck source or shuffled from the seeds
+    fn shuffle(&mut self, hand_number: u32) -> Result<ShuffledDeck, &'static str> {
+        if let Some(deck) = self.deck_source.deck_for_hand(hand_number)? {
+            return Ok((deck, None, None));
+        }
+
+        let client_seeds: Vec<String> = self.client_seeds.iter().flatten().cloned().collect();
+        let deck = shuffled_deck(self.server_seed.bytes(), &client_seeds);
+        let shuffle_proof = ShuffleProof {
+            hand_number,
+            commitment: self.server_seed.commitment(),
+            server_seed: self.server_seed.reveal(),
+            client_seeds,
+        };
+        Ok((deck, Some(shuffle_proof), Some(ServerSeed::new_seed())))
+    }
+
+    pub fn set_client_seed(&mut self, player_index: usize, seed: &str) -> Result<(), &'static str> {
+        if self.players_by_seats[player_index].is_none() {
+            return Err("seat empty");
+        }
+        if seed.len() > MAX_CLIENT_SEED_LENGTH {
+            return Err("client seed too long");
+        }
+        debug!("player at seat {} set client seed {}", player_index, seed);
+        self.emit(GameEvent::ClientSeedSet {
+            seat_index: player_index as u8,
+            seed: match seed.is_empty() {
+                true => None,
+                false => Some(seed.to_string()),
+            },
+        });
+        Ok(())
+    }
+
+    fn showdown_records(
+        &self,
+        boards: &[[Option<Card>; 5]],
+        contributions: &[Contribution],
+    ) -> Vec<ShowdownRecord> {
+        let contesting: Vec<usize> = contributions
+            .iter()
+            .filter(|contribution| contribution.contesting)
+            .map(|contribution| contribution.seat_index)
+            .collect();
+        // nobody shows his cards when everybody else folded
+        if contesting.len() < 2 || self.community_cards_shown < 5 {
+            return Vec::new();
+        }
+        contesting
+            .iter()
+            .filter_map(|seat| self.players_by_seats[*seat])
+            .map(|player| ShowdownRecord {
+                seat_index: player.seat_index,
+                cards: player.cards,
+                hands: boards
+                    .iter()
+                    .map(|board| match self.evaluate_hand(board, &player.cards) {
+                        Some(eval) => eval.to_string(),
+                        None => String::new(),
+                    })
+                    .collect(),
+            })
+            .collect()
     }
 
-    fn shuffle(&mut self) {
-        let mut rng = thread_rng();
-        // Knuth shuffle
-        for n in 0..52 {
-            let i = rng.gen_range(0..52 - n);
-            self.deck.swap(i, 51 - n);
+    fn evaluate_hand(&self, board: &[Option<Card>; 5], cards: &[Card; 2]) -> Option<Eval> {
+        let all_cards: Vec<EvaluatorCard> = board
+            .iter()
+            .flatten()
+            .chain(cards.iter())
+            .map(|card| card.to_evaluate())
+            .collect();
+        match self.evaluator.evaluate(all_cards) {
+            Err(_) => {
+                warn!("error evaluating hand");
+                None
+            }
+            Ok(eval) => Some(eval),
         }
     }
 
-    fn get_winner_seat(&self) -> usize {
-        let mut best_seat = 0;
+    // Seats holding the best hand on `board` among `eligible_seats`, ordered starting left of the dealer
+    // so that odd chips of a split pot go to the first of them.
+    fn winning_seats(&self, board: &[Option<Card>; 5], eligible_seats: &[usize]) -> Vec<usize> {
+        if eligible_seats.len() <= 1 {
+            return eligible_seats.to_vec();
+        }
+
+        let mut best_seats: Vec<usize> = Vec::new();
         let mut best_seat_hand: Option<Eval> = None;
-        let community_cards = self
-            .community_cards
-            .map(|card| card.expect("some community cards are None").to_evaluate())
-            .to_vec();
-        for seat_id in 0..self.max_players {
-            match self.players_by_seats[seat_id] {
+        for seat_id in eligible_seats {
+            let player = match self.players_by_seats[*seat_id] {
+                Some(player) => player,
+                None => continue,
+            };
+            match self.evaluate_hand(board, &player.cards) {
                 None => (),
-                Some(player) => match player.state {
-                    PlayerState::Folded | PlayerState::Left => (),
+                Some(eval) => match best_seat_hand {
+                    Some(prev_eval) if eval.is_equal_to(prev_eval) => best_seats.push(*seat_id),
+                    Some(prev_eval) if !eval.is_better_than(prev_eval) => (),
                     _ => {
-                        let private_cards = player.cards.map(|card| card.to_evaluate()).to_vec();
-                        let all_cards: Vec<EvaluatorCard> = community_cards
-                            .iter()
-                            .chain(private_cards.iter())
-                            .cloned()
-                            .collect();
-                        let current_hand = self.evaluator.evaluate(all_cards);
-                        match current_hand {
-                            Err(_) => println!("error evaluating hand"),
-                            Ok(eval) => match best_seat_hand {
-                                None => {
-                                    best_seat_hand = Some(eval);
-                                    best_seat = player.seat_index;
-                                }
-                                Some(prev_eval) => {
-                                    if eval.is_better_than(prev_eval) {
-                                        best_seat_hand = Some(eval);
-                                        best_seat = player.seat_index;
-                                    }
-                                }
-                            },
-                        }
+                        best_seat_hand = Some(eval);
+                        best_seats = vec![*seat_id];
                     }
                 },
             }
         }
 
-        best_seat as usize
+        best_seats.sort_by_key(|seat| {
+            (seat + self.max_players - self.dealer_seat - 1) % self.max_players
+        });
+        best_seats
+    }
+
+    // id of the player at the seat, nil for an empty one
+    fn player_at(&self, seat_index: usize) -> Uuid {
+        self.players
+            .iter()
+            .find(|(_, seat)| **seat == seat_index)
+            .map(|(player_id, _)| *player_id)
+            .unwrap_or_default()
+    }
+
+    // whether somebody at the table plays as `nickname`, whatever its case
+    pub fn seats_nickname(&self, nickname: &str) -> bool {
+        self.nicknames
+            .iter()
+            .flatten()
+            .any(|seated| seated.eq_ignore_ascii_case(nickname))
     }
 
     pub fn players_count(&self) -> u8 {
@@ -807,6 +1697,31 @@ impl Game {
         true
     }
 
+    fn unfolded_count(&self) -> usize {
+        self.players_by_seats
+            .iter()
+            .flatten()
+            .filter(|pl| !matches!(pl.state, PlayerState::Folded | PlayerState::Left))
+            .count()
+    }
+
+    fn betting_closed(&self) -> bool {
+        // nobody left to bet against: at most one player can still act and he already
+        // matched every bet
+        let max_bet = self.max_bet();
+        let mut can_act = self.players_by_seats.iter().flatten().filter(|pl| {
+            !matches!(
+                pl.state,
+                PlayerState::Folded | PlayerState::Left | PlayerState::AllIn
+            )
+        });
+        match (can_act.next(), can_act.next()) {
+            (None, _) => true,
+            (Some(pl), None) => pl.current_bet >= max_bet,
+            _ => false,
+        }
+    }
+
     fn round_end(&self) -> bool {
         for player in &self.players_by_seats {
             match player {
@@ -822,7 +1737,7 @@ impl Game {
     }
 }
 
-#[derive(Clone, Copy, Serialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum Color {
     Hearts,
     Diamonds,
@@ -840,9 +1755,39 @@ impl Color {
             Clubs => EvaluatorColor::Clubs,
         }
     }
+
+    pub const fn symbol(self) -> char {
+        use Color::*;
+        match self {
+            Hearts => 'h',
+            Diamonds => 'd',
+            Spades => 's',
+            Clubs => 'c',
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for Color {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Color, &'static str> {
+        match s {
+            "h" | "H" => Ok(Color::Hearts),
+            "d" | "D" => Ok(Color::Diamonds),
+            "s" | "S" => Ok(Color::Spades),
+            "c" | "C" => Ok(Color::Clubs),
+            _ => Err("invalid card color"),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Serialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum Rank {
     Two,
     Three,
@@ -878,17 +1823,66 @@ impl Rank {
             Ace => EvaluatorRank::Ace,
         }
     }
+
+    pub const fn symbol(self) -> char {
+        use Rank::*;
+        match self {
+            Two => '2',
+            Three => '3',
+            Four => '4',
+            Five => '5',
+            Six => '6',
+            Seven => '7',
+            Eight => '8',
+            Nine => '9',
+            Ten => 'T',
+            Jack => 'J',
+            Queen => 'Q',
+            King => 'K',
+            Ace => 'A',
+        }
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for Rank {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Rank, &'static str> {
+        use Rank::*;
+        match s {
+            "2" => Ok(Two),
+            "3" => Ok(Three),
+            "4" => Ok(Four),
+            "5" => Ok(Five),
+            "6" => Ok(Six),
+            "7" => Ok(Seven),
+            "8" => Ok(Eight),
+            "9" => Ok(Nine),
+            "T" | "t" | "10" => Ok(Ten),
+            "J" | "j" => Ok(Jack),
+            "Q" | "q" => Ok(Queen),
+            "K" | "k" => Ok(King),
+            "A" | "a" => Ok(Ace),
+            _ => Err("invalid card rank"),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Serialize, Debug)]
-enum GamePhase {
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum GamePhase {
     PreFlop, // every player has 2 cards, 0 community cards
     Flop,    // first 3 community cards
     Turn,    // 4th community card
     River,   // 5th community card on the table
 }
 
-#[derive(Clone, Copy, Serialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct Card {
     rank: Rank,
     color: Color,
@@ -899,14 +1893,592 @@ impl Card {
         Card { color, rank }
     }
 
+    // spades, hearts, diamonds and clubs, each from two to ace
+    pub fn ordered_deck() -> [Card; 52] {
+        let colors = [Color::Spades, Color::Hearts, Color::Diamonds, Color::Clubs];
+        let ranks = [
+            Rank::Two,
+            Rank::Three,
+            Rank::Four,
+            Rank::Five,
+            Rank::Six,
+            Rank::Seven,
+            Rank::Eight,
+            Rank::Nine,
+            Rank::Ten,
+            Rank::Jack,
+            Rank::Queen,
+            Rank::King,
+            Rank::Ace,
+        ];
+        let mut deck = [Card::new(Color::Spades, Rank::Two); 52];
+        for (idx, card) in deck.iter_mut().enumerate() {
+            *card = Card::new(colors[idx / 13], ranks[idx % 13]);
+        }
+        deck
+    }
+
+    pub fn rank(&self) -> Rank {
+        self.rank
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    // two characters, rank followed by color, i.e. "Ah" or "Td"
+    pub fn notation(&self) -> String {
+        self.to_string()
+    }
+
     pub fn to_evaluate(&self) -> EvaluatorCard {
         EvaluatorCard::new(self.rank.to_evaluate(), self.color.to_evaluate())
     }
 }
 
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.rank, self.color)
+    }
+}
+
+impl FromStr for Card {
+    type Err = &'static str;
+
+    // "Ah", "td" or "10c"
+    fn from_str(s: &str) -> Result<Card, &'static str> {
+        let s = s.trim();
+        if !s.is_ascii() || s.len() < 2 {
+            return Err("invalid card");
+        }
+        let (rank, color) = s.split_at(s.len() - 1);
+        Ok(Card::new(color.parse()?, rank.parse()?))
+    }
+}
+
+// card lists like "AsKd Qh" or "As, Kd, Qh", separators are optional
+pub fn parse_cards(s: &str) -> Result<Vec<Card>, &'static str> {
+    let symbols: Vec<char> = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .collect();
+    let mut cards = Vec::new();
+    let mut idx = 0;
+    while idx < symbols.len() {
+        // "10" takes one character more than the other ranks
+        let length = match symbols[idx..] {
+            ['1', '0', ..] => 3,
+            _ => 2,
+        };
+        if idx + length > symbols.len() {
+            return Err("invalid card");
+        }
+        let card: String = symbols[idx..idx + length].iter().collect();
+        cards.push(card.parse()?);
+        idx += length;
+    }
+    Ok(cards)
+}
+
+// use with #[serde(with = "card_notation")] to (de)serialize a card as "Ah" instead of
+// {"rank": "Ace", "color": "Hearts"}
+pub mod card_notation {
+    use super::Card;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(card: &Card, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(card)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Card, D::Error> {
+        let notation = String::deserialize(deserializer)?;
+        notation.parse().map_err(de::Error::custom)
+    }
+}
+
+// the same for card lists, written as a single string like "AsKdQh"
+pub mod cards_notation {
+    use super::{parse_cards, Card};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(cards: &[Card], serializer: S) -> Result<S::Ok, S::Error> {
+        let notation: String = cards.iter().map(|card| card.to_string()).collect();
+        serializer.serialize_str(&notation)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Card>, D::Error> {
+        let notation = String::deserialize(deserializer)?;
+        parse_cards(&notation).map_err(de::Error::custom)
+    }
+}
+
 #[derive(Clone, Copy, Serialize, Debug, PartialEq)]
 pub enum GamePlayState {
     NotStarted,
     Started,
     Ended,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::deck::DeckProvider;
+
+    // three players with 1000 chips and blinds of 5 and 10, seat 0 deals the first hand and
+    // acts first. Seats take two cards each from the top of the deck, the board comes next.
+    fn stacked_game(decks: &[&str]) -> Game {
+        seated(
+            Game::new_game(TableRecord::new_table(3, 5, 10, 1000)),
+            decks,
+        )
+    }
+
+    fn seated(game: Game, decks: &[&str]) -> Game {
+        let decks = decks
+            .iter()
+            .map(|deck| parse_cards(deck).unwrap())
+            .collect();
+        let mut game = game.with_deck_source(DeckSource::stacked(decks).unwrap());
+        for seat in 0..3 {
+            game.join_game(seat, &format!("player {}", seat), 0)
+                .unwrap();
+        }
+        for seat in 0..3 {
+            game.set_ready(seat, true).unwrap();
+        }
+        game
+    }
+
+    fn balances(game: &Game) -> Vec<u64> {
+        game.players_by_seats
+            .iter()
+            .flatten()
+            .map(|player| player.balance)
+            .collect()
+    }
+
+    #[test]
+    fn aces_lose_to_a_set_of_kings_on_a_king_high_flop() {
+        let mut game = stacked_game(&["AsAh KsKh 7c2d Kd8c3s 9h 4d"]);
+        assert_eq!(game.player_action(0, PlayerAction::AllIn, 0), 1);
+        assert_eq!(game.player_action(1, PlayerAction::Call, 0), 1);
+        assert_eq!(game.player_action(2, PlayerAction::Fold, 0), 1);
+
+        let history = &game.hand_histories()[0];
+        assert_eq!(history.pots.len(), 1);
+        assert_eq!(history.pots[0].amount, 2010);
+        assert_eq!(history.pots[0].winners, vec![1]);
+        // two players left with chips, the freezeout is over
+        assert_eq!(balances(&game), vec![0, 2010, 990]);
+        assert_eq!(game.game_state, GamePlayState::Ended);
+    }
+
+    #[test]
+    fn hands_are_found_by_the_player_id_not_the_nickname() {
+        let mut game = stacked_game(&["AsAh KsKh 7c2d Kd8c3s 9h 4d"]);
+        game.player_action(0, PlayerAction::AllIn, 0);
+        game.player_action(1, PlayerAction::Call, 0);
+        game.player_action(2, PlayerAction::Fold, 0);
+
+        let history = &game.hand_histories()[0];
+        for (player_id, seat) in &game.players {
+            assert_eq!(history.seat_of(*player_id), Some(*seat));
+        }
+        // somebody playing as "player 0" later is not the player of seat 0
+        assert_eq!(history.seat_of(Uuid::new_v4()), None);
+        // the ids are what sessions play with, the history never shows them
+        let shown = serde_json::to_string(history).unwrap();
+        assert!(game
+            .players
+            .keys()
+            .all(|player_id| !shown.contains(&player_id.to_string())));
+    }
+
+    #[test]
+    fn the_same_deck_shows_the_same_all_in_equities() {
+        let all_in_preflop = || {
+            let mut game = stacked_game(&["AsAh KsKh 7c2d"]);
+            game.player_action(0, PlayerAction::AllIn, 0);
+            game.player_action(1, PlayerAction::Call, 0);
+            game.player_action(2, PlayerAction::Fold, 0);
+            game.events()
+                .iter()
+                .find_map(|event| match event {
+                    GameEvent::HandsTabled { hands } => Some(hands.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let (first, second) = (all_in_preflop(), all_in_preflop());
+        assert_eq!(first.len(), 2);
+        // too many runouts before the flop to go through them all, they are sampled
+        assert!(first[0].equity.equity > 0.75 && first[0].equity.equity < 0.9);
+        for (first, second) in first.iter().zip(&second) {
+            assert_eq!(first.equity, second.equity);
+        }
+    }
+
+    #[test]
+    fn a_board_playing_for_everyone_splits_the_pot() {
+        let mut game = stacked_game(&["2c3d 4h5c 7c2d AsKsQs Js Ts"]);
+        assert_eq!(game.player_action(0, PlayerAction::AllIn, 0), 1);
+        assert_eq!(game.player_action(1, PlayerAction::Call, 0), 1);
+        assert_eq!(game.player_action(2, PlayerAction::Fold, 0), 1);
+
+        let history = &game.hand_histories()[0];
+        assert_eq!(history.pots[0].amount, 2010);
+        // listed from the left of the dealer, the first winners get the odd chips
+        assert_eq!(history.pots[0].winners, vec![1, 0]);
+        assert!(game.ledger().alerts().is_empty());
+    }
+
+    #[test]
+    fn an_uncalled_bet_goes_back_to_the_bettor() {
+        let game = Game::new_game(TableRecord {
+            hand_pause: 30,
+            ..TableRecord::new_table(3, 5, 10, 1000)
+        });
+        let mut game = seated(game, &["AsAh KsKh 7c2d"]);
+        assert_eq!(game.player_action(0, PlayerAction::Bet, 100), 1);
+        assert_eq!(game.player_action(1, PlayerAction::Fold, 0), 1);
+        assert_eq!(game.player_action(2, PlayerAction::Fold, 0), 1);
+
+        let history = &game.hand_histories()[0];
+        let returned = history.actions.last().unwrap();
+        assert_eq!(
+            (returned.seat_index, returned.action, returned.amount),
+            (0, HandAction::UncalledBetReturned, 90)
+        );
+        // only the called part and the blinds make the pot
+        assert_eq!(history.pots[0].amount, 25);
+        assert_eq!(history.pots[0].winners, vec![0]);
+        assert_eq!(balances(&game), vec![1015, 995, 990]);
+        assert!(game.ledger().alerts().is_empty());
+    }
+
+    #[test]
+    fn a_shorter_all_in_call_leaves_the_rest_of_the_bet_uncalled() {
+        let game = Game::new_game(TableRecord {
+            hand_pause: 30,
+            ..TableRecord::new_table(3, 5, 10, 1000)
+        });
+        let mut game = seated(game, &["2c3d 4h5c 7c2d", "AsAh KsKh 7c2d Qd8c3s 9h 4d"]);
+        // seat 0 takes the blinds first, its stack is the bigger one for the next hand
+        game.player_action(0, PlayerAction::Bet, 100);
+        game.player_action(1, PlayerAction::Fold, 0);
+        game.player_action(2, PlayerAction::Fold, 0);
+        assert_eq!(balances(&game), vec![1015, 995, 990]);
+
+        assert!(game.deal_next_hand());
+        game.player_action(1, PlayerAction::AllIn, 0);
+        game.player_action(2, PlayerAction::Fold, 0);
+        game.player_action(0, PlayerAction::AllIn, 0);
+
+        let history = &game.hand_histories()[1];
+        let returned = history.actions.last().unwrap();
+        assert_eq!(
+            (returned.seat_index, returned.action, returned.amount),
+            (0, HandAction::UncalledBetReturned, 20)
+        );
+        assert_eq!(history.pots.len(), 1);
+        assert_eq!(history.pots[0].amount, 1995);
+        assert_eq!(history.pots[0].winners, vec![0]);
+        assert_eq!(balances(&game), vec![2015, 0, 985]);
+        assert!(game.ledger().alerts().is_empty());
+    }
+
+    fn rabbit_hunting(rabbit_hunt: bool) -> Game {
+        let game = Game::new_game(TableRecord {
+            rabbit_hunt,
+            hand_pause: 30,
+            ..TableRecord::new_table(3, 5, 10, 1000)
+        });
+        seated(game, &["AsAh KsKh 7c2d Kd8c3s 9h 4d"])
+    }
+
+    #[test]
+    fn the_rabbit_hunt_shows_the_board_nobody_saw() {
+        let mut game = rabbit_hunting(true);
+        game.player_action(0, PlayerAction::Fold, 0);
+        game.player_action(1, PlayerAction::Fold, 0);
+        let summary = game.last_hand.clone().unwrap();
+        assert_eq!(summary.boards[0], [None; 5]);
+        assert_eq!(summary.rabbit_cards, parse_cards("Kd8c3s 9h 4d").unwrap());
+
+        // only the turn and the river after a flop
+        let mut game = rabbit_hunting(true);
+        game.player_action(0, PlayerAction::Call, 0);
+        game.player_action(1, PlayerAction::Call, 0);
+        game.player_action(2, PlayerAction::Check, 0);
+        game.player_action(1, PlayerAction::Bet, 50);
+        game.player_action(2, PlayerAction::Fold, 0);
+        game.player_action(0, PlayerAction::Fold, 0);
+        let summary = game.last_hand.clone().unwrap();
+        let flop = parse_cards("Kd8c3s").unwrap();
+        assert_eq!(
+            summary.boards[0],
+            [Some(flop[0]), Some(flop[1]), Some(flop[2]), None, None]
+        );
+        assert_eq!(summary.rabbit_cards, parse_cards("9h 4d").unwrap());
+        // the hand histories keep to the cards dealt
+        assert_eq!(game.hand_histories()[0].boards, summary.boards);
+    }
+
+    #[test]
+    fn no_rabbit_hunt_unless_the_table_has_it() {
+        let mut game = rabbit_hunting(false);
+        game.player_action(0, PlayerAction::Fold, 0);
+        game.player_action(1, PlayerAction::Fold, 0);
+        assert!(game.last_hand.clone().unwrap().rabbit_cards.is_empty());
+
+        // nothing is left to hunt after the river
+        let mut game = rabbit_hunting(true);
+        game.player_action(0, PlayerAction::AllIn, 0);
+        game.player_action(1, PlayerAction::Call, 0);
+        game.player_action(2, PlayerAction::Fold, 0);
+        assert!(game.last_hand.clone().unwrap().rabbit_cards.is_empty());
+    }
+
+    #[test]
+    fn stacked_decks_are_dealt_in_turn() {
+        let mut game = stacked_game(&["AsAh KsKh 7c2d", "2c3d 4h5c 7s7h"]);
+        assert_eq!(
+            game.players_by_seats[0].unwrap().cards,
+            [Card::from_str("As").unwrap(), Card::from_str("Ah").unwrap()]
+        );
+        // the blinds take the pot, the next hand comes from the second deck
+        assert_eq!(game.player_action(0, PlayerAction::Fold, 0), 1);
+        assert_eq!(game.player_action(1, PlayerAction::Fold, 0), 1);
+        assert_eq!(game.hand_number(), 2);
+        assert_eq!(
+            game.players_by_seats[2].unwrap().cards,
+            [Card::from_str("7s").unwrap(), Card::from_str("7h").unwrap()]
+        );
+    }
+
+    #[test]
+    fn only_the_seats_of_the_table_can_be_joined() {
+        let mut game = Game::new_game(TableRecord::new_table(3, 5, 10, 1000));
+        assert_eq!(game.join_game(3, "player 3", 0), Err("no such seat"));
+        assert_eq!(
+            game.join_game(u8::MAX, "player 255", 0),
+            Err("no such seat")
+        );
+        assert!(game.join_game(2, "player 2", 0).is_ok());
+        assert_eq!(game.join_game(2, "player 2", 0), Err("seat already taken"));
+    }
+
+    #[test]
+    fn the_next_hand_waits_for_the_pause() {
+        let game = Game::new_game(TableRecord {
+            hand_pause: 30,
+            ..TableRecord::new_table(3, 5, 10, 1000)
+        });
+        let mut game = seated(game, &["AsAh KsKh 7c2d"]);
+        assert_eq!(game.next_hand_at(), None);
+        game.player_action(0, PlayerAction::Fold, 0);
+        game.player_action(1, PlayerAction::Fold, 0);
+
+        assert!(!game.hand_in_progress());
+        assert_eq!(game.hand_number(), 1);
+        assert!(game.next_hand_at().unwrap() >= unix_now() + 29);
+        assert!(game.deal_next_hand());
+        assert_eq!(game.hand_number(), 2);
+        assert_eq!(game.next_hand_at(), None);
+        assert!(!game.deal_next_hand());
+    }
+
+    #[test]
+    fn a_busted_player_buys_back_in_during_the_pause() {
+        let busted = || {
+            let game = Game::new_game(TableRecord {
+                buy_in_model: BuyInModel::Cash { max_buy_in: 2000 },
+                hand_pause: 10,
+                ..TableRecord::new_table(3, 5, 10, 1000)
+            });
+            let mut game = seated(game, &["AsAh KsKh 7c2d Kd8c3s 9h 4d"]);
+            game.player_action(0, PlayerAction::AllIn, 0);
+            game.player_action(1, PlayerAction::Call, 0);
+            game.player_action(2, PlayerAction::Fold, 0);
+            assert_eq!(balances(&game), vec![0, 2010, 990]);
+            game
+        };
+
+        // two players left with chips, the game is over once nobody bought back in
+        let mut game = busted();
+        assert_eq!(game.game_state, GamePlayState::Started);
+        game.deal_next_hand();
+        assert_eq!(game.game_state, GamePlayState::Ended);
+
+        let mut game = busted();
+        assert_eq!(game.buy_chips(0, BuyInKind::TopUp, 1000), Ok(1000));
+        assert_eq!(game.players_by_seats[0].unwrap().balance, 1000);
+        game.deal_next_hand();
+        assert_eq!(game.game_state, GamePlayState::Started);
+        assert_eq!(game.hand_number(), 2);
+        assert_ne!(game.players_by_seats[0].unwrap().state, PlayerState::Folded);
+        assert!(game.ledger().alerts().is_empty());
+    }
+
+    #[test]
+    fn a_new_player_at_a_vacated_seat_has_their_own_add_on() {
+        let mut game = Game::new_game(TableRecord {
+            buy_in_model: BuyInModel::Tournament {
+                rebuy_hands: 0,
+                add_on: 500,
+            },
+            ..TableRecord::new_table(3, 5, 10, 1000)
+        });
+        let first = game.join_game(0, "first", 0).unwrap();
+        assert_eq!(game.buy_chips(0, BuyInKind::AddOn, 0), Ok(500));
+        assert_eq!(
+            game.buy_chips(0, BuyInKind::AddOn, 0),
+            Err("add-on already taken")
+        );
+        assert_eq!(game.cash_out(first), Ok(1500));
+
+        game.join_game(0, "second", 0).unwrap();
+        assert_eq!(game.buy_chips(0, BuyInKind::AddOn, 0), Ok(500));
+        assert!(game.ledger().alerts().is_empty());
+    }
+
+    #[test]
+    fn blinds_double_from_the_next_hand_on() {
+        let game = Game::new_game(TableRecord {
+            level_duration: 600,
+            ..TableRecord::new_table(3, 5, 10, 1000)
+        });
+        let mut game = seated(game, &["AsAh KsKh 7c2d"]);
+        assert!(game.next_level_at().unwrap() >= unix_now() + 599);
+        game.raise_blinds();
+        assert_eq!((game.small_blind, game.big_blind), (10, 20));
+        assert_eq!(game.current_hand.as_ref().unwrap().table.big_blind, 10);
+
+        game.player_action(0, PlayerAction::Fold, 0);
+        game.player_action(1, PlayerAction::Fold, 0);
+        // seat 2 won the blinds of 5 and 10, then posts the small blind of the next hand
+        assert_eq!(game.current_hand.as_ref().unwrap().table.big_blind, 20);
+        assert_eq!(balances(&game), vec![980, 995, 995]);
+        let rebuilt = Game::from_events(game.events()).unwrap();
+        assert_eq!((rebuilt.big_blind, rebuilt.blind_level), (20, 1));
+        assert_eq!(rebuilt.next_level_at(), game.next_level_at());
+    }
+
+    // what every seat is shown, with the hands and the chips gone through
+    fn seen_by_everyone(game: &Game) -> String {
+        let mut players: Vec<(&Uuid, &usize)> = game.players.iter().collect();
+        players.sort_by_key(|(_, seat)| **seat);
+        let states: Vec<GameState> = players
+            .iter()
+            .map(|(player_id, _)| game.collect_state_data(**player_id))
+            .collect();
+        serde_json::to_string(&(states, game.hand_histories(), game.ledger())).unwrap()
+    }
+
+    #[test]
+    fn a_game_rebuilt_from_its_events_plays_on_the_same() {
+        let decks = ["AsAh KsKh 7c2d", "2c3d 4h5c 7s7h Kd8c3s 9h 4d"];
+        let mut game = stacked_game(&decks);
+        game.player_action(0, PlayerAction::Bet, 100);
+        game.player_action(1, PlayerAction::Fold, 0);
+        game.player_action(2, PlayerAction::Fold, 0);
+        // the second hand stops on the flop
+        game.player_action(1, PlayerAction::Call, 0);
+        game.player_action(2, PlayerAction::Call, 0);
+        game.player_action(0, PlayerAction::Check, 0);
+        assert_eq!(game.hand_number(), 2);
+
+        // the deck source is not in the log
+        let stacked = decks
+            .iter()
+            .map(|deck| parse_cards(deck).unwrap())
+            .collect();
+        let mut rebuilt = Game::from_events(game.events())
+            .unwrap()
+            .with_deck_source(DeckSource::stacked(stacked).unwrap());
+        assert_eq!(rebuilt.events().len(), game.events().len());
+        assert_eq!(seen_by_everyone(&rebuilt), seen_by_everyone(&game));
+        assert_eq!(rebuilt.active_seat(), game.active_seat());
+        assert_eq!(balances(&rebuilt), balances(&game));
+
+        let seat = game.active_seat();
+        for game in [&mut game, &mut rebuilt] {
+            assert_eq!(game.player_action(seat, PlayerAction::Bet, 50), 1);
+        }
+        assert_eq!(seen_by_everyone(&rebuilt), seen_by_everyone(&game));
+        assert!(Game::from_events(&game.events()[1..]).is_err());
+    }
+
+    #[test]
+    fn stacked_decks_take_every_card_once() {
+        assert!(DeckSource::stacked(vec![parse_cards("AsKsAs").unwrap()]).is_err());
+        assert!(DeckSource::stacked(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn cards_read_back_from_their_notation() {
+        let ten = Card::from_str("Th").unwrap();
+        assert_eq!(ten, Card::new(Color::Hearts, Rank::Ten));
+        assert_eq!(ten.to_string(), "Th");
+        assert_eq!(Card::from_str("10c").unwrap().to_string(), "Tc");
+        assert_eq!(Card::from_str(&ten.to_string()).unwrap(), ten);
+        assert!(Card::from_str("Tx").is_err());
+        assert!(Card::from_str("1c").is_err());
+
+        let cards = parse_cards("AsKd Qh").unwrap();
+        assert_eq!(cards, parse_cards("As, Kd, Qh").unwrap());
+        let notation: String = cards.iter().map(|card| card.notation()).collect();
+        assert_eq!(notation, "AsKdQh");
+        assert_eq!(parse_cards(&notation).unwrap(), cards);
+        assert_eq!(
+            parse_cards("10c 10d").unwrap(),
+            parse_cards("TcTd").unwrap()
+        );
+        assert!(parse_cards("AsK").is_err());
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Compact {
+        #[serde(with = "card_notation")]
+        card: Card,
+        #[serde(with = "cards_notation")]
+        board: Vec<Card>,
+    }
+
+    #[test]
+    fn compact_notation_goes_through_serde() {
+        let compact = Compact {
+            card: Card::from_str("10c").unwrap(),
+            board: parse_cards("AsKd Qh").unwrap(),
+        };
+        let json = serde_json::to_string(&compact).unwrap();
+        assert_eq!(json, r#"{"card":"Tc","board":"AsKdQh"}"#);
+        assert_eq!(serde_json::from_str::<Compact>(&json).unwrap(), compact);
+        assert!(serde_json::from_str::<Compact>(r#"{"card":"Tc","board":"AsKdQ"}"#).is_err());
+    }
+
+    struct BrokenDeck;
+
+    impl DeckProvider for BrokenDeck {
+        fn deck_for_hand(&mut self, _hand_number: u32) -> [Card; 52] {
+            let mut deck = Card::ordered_deck();
+            deck[51] = deck[0];
+            deck
+        }
+    }
+
+    #[test]
+    fn nothing_is_dealt_from_a_broken_custom_deck() {
+        let mut game = Game::new_game(TableRecord::new_table(3, 5, 10, 1000))
+            .with_deck_source(DeckSource::custom(BrokenDeck));
+        for seat in 0..3 {
+            game.join_game(seat, &format!("player {}", seat), 0)
+                .unwrap();
+            game.set_ready(seat as usize, true).unwrap();
+        }
+        assert_eq!(game.game_state, GamePlayState::Started);
+        assert_eq!(game.hand_number(), 0);
+        assert!(game.paused());
+        assert_eq!(balances(&game), vec![1000, 1000, 1000]);
+    }
+}
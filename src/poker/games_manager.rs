@@ -1,76 +1,203 @@
 use std::collections::HashMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::poker::buy_in::BuyInModel;
+use crate::poker::equity::TabledHand;
+use crate::poker::fairness::ShuffleProof;
 use crate::poker::game::{Game, Card};
+use crate::poker::hints::TrainingHints;
+use crate::poker::history::TableRecord;
 use crate::poker::player::{PlayerData};
+use crate::poker::reaper::ReaperConfig;
+use crate::poker::runout::RunoutVote;
+use crate::poker::stats::{StatsBook, StatsReport};
+use crate::poker::storage::GameStore;
+use crate::poker::table::{Reply, TableCommand, TableHandle, spawn_table};
 use uuid::Uuid;
+use log::{info, warn};
 
 // use crate::poker::player;
 
+use std::io;
 use std::sync::{RwLock, Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::game::GamePlayState;
 
-#[derive(Clone)]
+// Directory of the running tables, its lock is only held to find, add or remove them
 pub struct GamesManager {
-    games: HashMap<Uuid, Game>
+    games: RwLock<HashMap<Uuid, TableHandle>>,
+    store: Option<GameStore>,
+    stats: StatsBook,
+    shutting_down: AtomicBool
 }
 
 impl GamesManager {
     pub fn new_manager() -> GamesManager {
-        let games: HashMap<Uuid, Game> = HashMap::with_capacity(100);
-        GamesManager{games}
+        let games: HashMap<Uuid, TableHandle> = HashMap::with_capacity(100);
+        GamesManager{games: RwLock::new(games), store: None, stats: StatsBook::new_book(), shutting_down: AtomicBool::new(false)}
     }
 
-    pub fn new_game(&mut self, max_players: usize, small_blind: u64, big_blind: u64, initial_balance: u64) -> Uuid {
-        let game_id = Uuid::new_v4();
-        let game = Game::new_game(max_players, small_blind, big_blind, initial_balance);
-        self.games.insert(game_id, game);
-        game_id
+    // manager saving its games to the store, starting with the ones already there
+    pub fn with_store(store: GameStore) -> io::Result<GamesManager> {
+        let mut manager = GamesManager::new_manager();
+        manager.stats = StatsBook::with_store(store.clone())?;
+        for (game_id, events) in store.load_games()? {
+            match Game::from_events(&events) {
+                Err(err) => warn!("could not restore game {}: {}", game_id, err),
+                Ok(mut game) => {
+                    // games paused by the last shutdown deal again
+                    game.resume();
+                    let table = spawn_table(game_id, game, Some(store.clone()), events.len(), manager.stats.clone());
+                    manager.games.get_mut().unwrap().insert(game_id, table);
+                }
+            }
+        }
+        info!("restored {} games from {}", manager.games.get_mut().unwrap().len(), store.dir().display());
+        manager.store = Some(store);
+        Ok(manager)
+    }
+
+    // snapshot of the directory, so it is not locked while waiting for tables
+    fn tables(&self) -> Vec<(Uuid, TableHandle)> {
+        self.games.read().unwrap().iter().map(|(game_id, table)| (*game_id, table.clone())).collect()
     }
 
-    pub fn get_game(&self, game_id: Uuid) -> Result<&Game, &str> {
-        let game = self.games.get(&game_id);
-        match game {
+    pub fn table(&self, game_id: Uuid) -> Result<TableHandle, &'static str> {
+        match self.games.read().unwrap().get(&game_id) {
             None => Err("game not found"),
-            Some(game) => Ok(game)
+            Some(table) => Ok(table.clone())
         }
     }
 
-    pub fn get_game_mut(&mut self, game_id: Uuid) -> Result<&mut Game, &str> {
-        let game = self.games.get_mut(&game_id);
-        match game {
-            None => Err("game not found"),
-            Some(game) => Ok(game)
+    // sends a command to the table of the game and waits for its answer
+    pub async fn request<T>(&self, game_id: Uuid, command: impl FnOnce(Reply<T>) -> TableCommand) -> Result<T, &'static str> {
+        let table = self.table(game_id)?;
+        table.request(command).await
+    }
+
+    // runs f on the game between the commands of its table
+    pub async fn inspect<T: Send + 'static>(&self, game_id: Uuid, f: impl FnOnce(&Game) -> T + Send + 'static) -> Result<T, &'static str> {
+        let table = self.table(game_id)?;
+        table.inspect(f).await
+    }
+
+    // Stops taking new games and players and pauses every game, hands in progress can still be finished.
+    pub async fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        for (_, table) in self.tables() {
+            let _ = table.request(|reply| TableCommand::Pause{reply}).await;
         }
     }
 
-    pub fn get_all_games_data(&self) -> Vec<GameData> {
+    pub fn shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    pub async fn hands_in_progress(&self) -> usize {
+        let mut in_progress = 0;
+        for (_, table) in self.tables() {
+            if let Ok(true) = table.inspect(|game| game.hand_in_progress()).await {
+                in_progress += 1;
+            }
+        }
+        in_progress
+    }
+
+    // Asks every table to archive itself when it expired and forgets the ones that did.
+    // Nothing is reaped while shutting down, the next start gives every game a fresh ttl.
+    pub async fn reap(&self, config: &ReaperConfig) -> usize {
+        let mut reaped = 0;
+        for (game_id, table) in self.tables() {
+            if self.shutting_down() {
+                break;
+            }
+            if let Ok(Some(_)) = table.request(|reply| TableCommand::Reap{config: *config, reply}).await {
+                self.games.write().unwrap().remove(&game_id);
+                reaped += 1;
+            }
+        }
+        reaped
+    }
+
+    pub fn new_game(&self, table: TableRecord) -> Result<Uuid, &str> {
+        table.buy_in_model.validate(table.initial_balance)?;
+        if !(1..=3).contains(&table.max_boards) {
+            return Err("max boards must be between 1 and 3");
+        }
+        let game_id = Uuid::new_v4();
+        let game = Game::new_game(table);
+        let table = spawn_table(game_id, game, self.store.clone(), 0, self.stats.clone());
+        self.games.write().unwrap().insert(game_id, table);
+        Ok(game_id)
+    }
+
+    pub async fn get_all_games_data(&self) -> Vec<GameData> {
         let mut all_games_data: Vec<GameData> = Vec::new();
 
-        for (game_id, game) in &self.games {
-            let game_data = GameData{
-                game_id: *game_id,
-                seats_count: game.max_players, 
-                seats_occupied: game.players_count(),
-                small_blind: game.small_blind,
-                big_blind: game.big_blind,
-                initial_balance: game.initial_balance,
-                game_state: game.game_state
-            };
-            all_games_data.push(game_data);
+        for (game_id, table) in self.tables() {
+            // a table reaped meanwhile is left out
+            if let Ok(game_data) = table.inspect(move |game| game_data(game_id, game)).await {
+                all_games_data.push(game_data);
+            }
         }
 
         all_games_data
     }
 
-    pub fn get_game_state(&self, game_id: Uuid, player_id: Uuid) -> Result<GameState, &str> {
-        let game = self.games.get(&game_id);
+    pub async fn get_game_state(&self, game_id: Uuid, player_id: Uuid) -> Result<GameState, &str> {
+        self.inspect(game_id, move |game| game.collect_state_data(player_id)).await
+    }
+
+    // over every hand played at any table, None before the first one
+    pub fn player_stats(&self, nickname: &str) -> Option<StatsReport> {
+        self.stats.report(nickname)
+    }
 
-        match game {
-            None => return Err("game not found"),
-            Some(game) => Ok(game.collect_state_data(player_id))
+    // a nickname seated at a table or with hands counted, an account taking it would inherit them
+    pub async fn nickname_in_use(&self, nickname: &str) -> bool {
+        if self.stats.knows(nickname) {
+            return true;
+        }
+        for (_, table) in self.tables() {
+            let nickname = nickname.to_string();
+            if let Ok(true) = table.inspect(move |game| game.seats_nickname(&nickname)).await {
+                return true;
+            }
         }
+        false
+    }
+
+    // writes the stats recorded so far, blocking
+    pub fn flush_stats(&self) {
+        self.stats.flush();
+    }
+
+    // stats of everybody seated at the table but the asker
+    pub fn add_hud(&self, state: &mut GameState) {
+        state.hud = state.players.iter().map(|player| match player {
+            Some(player) if state.asker_seat != Some(player.seat_index as usize) => self.stats.report(&player.nickname),
+            _ => None
+        }).collect();
+    }
+}
+
+pub fn game_data(game_id: Uuid, game: &Game) -> GameData {
+    GameData{
+        game_id,
+        seats_count: game.max_players, 
+        seats_occupied: game.players_count(),
+        small_blind: game.small_blind,
+        big_blind: game.big_blind,
+        initial_balance: game.initial_balance,
+        buy_in_model: game.buy_in_model,
+        max_boards: game.max_boards,
+        rabbit_hunt: game.rabbit_hunt,
+        bet_time: game.action_clock,
+        training: game.training,
+        hand_pause: game.hand_pause,
+        level_duration: game.level_duration,
+        game_state: game.game_state
     }
 }
 
@@ -82,6 +209,13 @@ pub struct GameData {
     pub small_blind: u64,
     pub big_blind: u64,
     pub initial_balance: u64,
+    pub buy_in_model: BuyInModel,
+    pub max_boards: u8,
+    pub rabbit_hunt: bool,
+    pub bet_time: u64,
+    pub training: bool,
+    pub hand_pause: u64,
+    pub level_duration: u64,
     pub game_state: GamePlayState
 }
 
@@ -90,16 +224,92 @@ pub struct GameState {
     pub asker_seat: Option<usize>,
     pub active_seat: usize,
     pub community_cards: [Option<Card>; 5],
+    pub boards: Vec<[Option<Card>; 5]>, // community_cards followed by extra runouts
+    pub runout_vote: Option<RunoutVote>,
+    pub tabled_hands: Vec<TabledHand>, // cards shown with their chances once the betting closed before the river
+    pub hints: Option<TrainingHints>, // own hand only, at training tables while in a hand
+    pub hand_commitment: Option<String>, // sha256 of the server seed shuffling the current hand
+    pub next_hand_commitment: Option<String>, // only for provably fair decks
+    pub last_hand: Option<HandSummary>,
     pub personal_cards: [Option<Card>; 2],
     pub bets_placed: Vec<Option<u64>>, // indexed by seats
     pub pot: u64,
     pub players: Vec<Option<PlayerData>>,
+    pub hud: Vec<Option<StatsReport>>, // indexed by seats, only filled when asked for
     pub small_blind: u64,
     pub big_blind: u64,
+    pub blind_level: u32, // times the blinds doubled
+    pub next_level_at: Option<u64>, // unix time the blinds double at, None with fixed blinds
+    pub next_hand_at: Option<u64>, // unix time of the next hand while the table pauses between hands
     pub game_state: GamePlayState,
+    pub paused: bool, // no new hands until the server restarts
+    pub hand_number: u32,
     pub dealer_seat: usize,
     pub small_blind_seat: usize,
     pub big_blind_seat: usize
 }
 
-pub type GamesManagerArc = Arc<RwLock<GamesManager>>;
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PotResult {
+    pub amount: u64,
+    pub board: usize,
+    pub winners: Vec<usize>,
+    pub eligible_seats: Vec<usize>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HandSummary {
+    pub hand_number: u32,
+    pub boards: Vec<[Option<Card>; 5]>, // cards dealt to the table, every runout is a separate board
+    pub rabbit_cards: Vec<Card>, // rest of the board when the hand ended early and rabbit hunting is on
+    pub pots: Vec<PotResult>, // one entry per pot and board
+    pub shuffle_proof: Option<ShuffleProof>,
+    #[serde(default)]
+    pub tabled_hands: Vec<TabledHand> // hands shown at an all-in and their chances then
+}
+
+pub type GamesManagerArc = Arc<GamesManager>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(seat_index: u8, nickname: &str) -> impl FnOnce(Reply<Result<Uuid, &'static str>>) -> TableCommand {
+        let nickname = nickname.to_string();
+        move |reply| TableCommand::Join{seat_index, nickname, appearance_type: 0, reply}
+    }
+
+    #[tokio::test]
+    async fn commands_to_one_game_are_taken_in_turn() {
+        let manager = GamesManager::new_manager();
+        let game_id = manager.new_game(TableRecord::new_table(3, 5, 10, 1000)).unwrap();
+        let other_id = manager.new_game(TableRecord::new_table(3, 5, 10, 1000)).unwrap();
+
+        // both ask for the same seat at once, the table seats the first one only
+        let (first, second, elsewhere) = tokio::join!(
+            manager.request(game_id, join(0, "alice")),
+            manager.request(game_id, join(0, "bob")),
+            manager.request(other_id, join(0, "bob"))
+        );
+        assert!(first.unwrap().is_ok());
+        assert_eq!(second.unwrap(), Err("seat already taken"));
+        assert!(elsewhere.unwrap().is_ok());
+        let seated = manager.inspect(game_id, |game| game.players_count()).await;
+        assert_eq!(seated, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn a_table_at_work_leaves_the_directory_free() {
+        let manager = Arc::new(GamesManager::new_manager());
+        let game_id = manager.new_game(TableRecord::new_table(3, 5, 10, 1000)).unwrap();
+
+        // adding a game takes the directory's lock, it would never come back if requests held it
+        let directory = manager.clone();
+        let created = manager.inspect(game_id, move |_| {
+            directory.new_game(TableRecord::new_table(2, 5, 10, 1000)).map_err(|err| err.to_string())
+        }).await.unwrap().unwrap();
+        assert!(manager.table(created).is_ok());
+        assert_eq!(manager.get_all_games_data().await.len(), 2);
+        assert_eq!(manager.request(Uuid::new_v4(), join(0, "alice")).await, Err("game not found"));
+    }
+}
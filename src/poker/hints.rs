@@ -0,0 +1,116 @@
+use crate::poker::game::{cards_notation, parse_cards, Card};
+use poker::{Eval, Evaluator};
+use serde::Serialize;
+
+// The weakest five cards of every hand category from a pair up to a straight flush, a hand is in
+// the highest category whose floor it reaches
+const CATEGORY_FLOORS: [&str; 8] = [
+    "2s2h3d4c5s", // pair
+    "3s3h2d2c4s", // two pair
+    "2s2h2d3c4s", // three of a kind
+    "As2h3d4c5s", // straight
+    "2s3s4s5s7s", // flush
+    "2s2h2d3c3s", // full house
+    "2s2h2d2c3s", // four of a kind
+    "As2s3s4s5s", // straight flush
+];
+
+// Live help for a learner, computed from their own cards and the board they can see only
+#[derive(Clone, Serialize, Debug)]
+pub struct TrainingHints {
+    pub made_hand: Option<String>, // best five cards so far, None before the flop
+    #[serde(with = "cards_notation")]
+    pub outs: Vec<Card>, // unseen cards lifting the hand to a better category than the board's, as "AhKd"
+    pub improve_chance: f64, // share of the unseen cards that are outs
+    pub to_call: u64,
+    pub pot_odds: f64, // share of the pot after calling that the call costs, 0 with nothing to call
+}
+
+fn category(floors: &[Eval], eval: Eval) -> usize {
+    floors
+        .iter()
+        .filter(|floor| eval.is_better_than(**floor) || eval.is_equal_to(**floor))
+        .count()
+}
+
+// what the board makes for everybody, boards under five cards only pair up
+fn board_category(evaluator: &Evaluator, floors: &[Eval], board: &[Card]) -> usize {
+    if let Some(eval) = evaluate(evaluator, board) {
+        return category(floors, eval);
+    }
+    let mut counts = [0; 13];
+    for card in board {
+        counts[card.rank() as usize] += 1;
+    }
+    let pairs = counts.iter().filter(|count| **count == 2).count();
+    match counts.iter().max() {
+        Some(4) => 7,
+        Some(3) => 3,
+        _ => pairs,
+    }
+}
+
+fn evaluate(evaluator: &Evaluator, cards: &[Card]) -> Option<Eval> {
+    evaluator
+        .evaluate(
+            cards
+                .iter()
+                .map(|card| card.to_evaluate())
+                .collect::<Vec<_>>(),
+        )
+        .ok()
+}
+
+// Outs are only counted on the flop and the turn, there is no made hand before the flop and no card
+// coming after the river
+pub fn training_hints(
+    evaluator: &Evaluator,
+    cards: &[Card; 2],
+    board: &[Card],
+    to_call: u64,
+    pot: u64,
+) -> TrainingHints {
+    let mut known: Vec<Card> = cards.to_vec();
+    known.extend_from_slice(board);
+    let made = match board.len() >= 3 {
+        true => evaluate(evaluator, &known),
+        false => None,
+    };
+
+    let mut outs = Vec::new();
+    let unseen: Vec<Card> = Card::ordered_deck()
+        .into_iter()
+        .filter(|card| !known.contains(card))
+        .collect();
+    if let (Some(made), 3 | 4) = (made, board.len()) {
+        let floors: Vec<Eval> = CATEGORY_FLOORS
+            .iter()
+            .filter_map(|floor| evaluate(evaluator, &parse_cards(floor).unwrap()))
+            .collect();
+        let current = category(&floors, made);
+        let mut next_board = board.to_vec();
+        for card in &unseen {
+            known.push(*card);
+            next_board.push(*card);
+            if let Some(next) = evaluate(evaluator, &known) {
+                let next = category(&floors, next);
+                if next > current && next > board_category(evaluator, &floors, &next_board) {
+                    outs.push(*card);
+                }
+            }
+            known.pop();
+            next_board.pop();
+        }
+    }
+
+    TrainingHints {
+        made_hand: made.map(|eval| eval.to_string()),
+        improve_chance: outs.len() as f64 / unseen.len() as f64,
+        outs,
+        to_call,
+        pot_odds: match to_call {
+            0 => 0.0,
+            _ => to_call as f64 / (pot + to_call) as f64,
+        },
+    }
+}
@@ -0,0 +1,179 @@
+use crate::poker::buy_in::BuyInModel;
+use crate::poker::game::{Card, GamePhase};
+use crate::poker::games_manager::PotResult;
+use crate::poker::player::PlayerAction;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum HandAction {
+    SmallBlind,
+    BigBlind,
+    Check,
+    Call,
+    Bet,
+    AllIn,
+    Fold,
+    RunIt,               // amount is the number of boards the player agreed to
+    UncalledBetReturned, // amount went back to the player
+}
+
+impl HandAction {
+    pub const fn from_player_action(action: PlayerAction) -> HandAction {
+        match action {
+            PlayerAction::Check => HandAction::Check,
+            PlayerAction::Call => HandAction::Call,
+            PlayerAction::Bet => HandAction::Bet,
+            PlayerAction::Fold => HandAction::Fold,
+            PlayerAction::AllIn => HandAction::AllIn,
+            PlayerAction::RunIt => HandAction::RunIt,
+        }
+    }
+
+    // None for the actions the engine takes on its own
+    pub const fn to_player_action(self) -> Option<PlayerAction> {
+        match self {
+            HandAction::Check => Some(PlayerAction::Check),
+            HandAction::Call => Some(PlayerAction::Call),
+            HandAction::Bet => Some(PlayerAction::Bet),
+            HandAction::AllIn => Some(PlayerAction::AllIn),
+            HandAction::Fold => Some(PlayerAction::Fold),
+            HandAction::RunIt => Some(PlayerAction::RunIt),
+            HandAction::SmallBlind | HandAction::BigBlind | HandAction::UncalledBetReturned => None,
+        }
+    }
+}
+
+// Settings of a table, given when the game is created and kept with every hand
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct TableRecord {
+    pub max_players: usize,
+    pub small_blind: u64,
+    pub big_blind: u64,
+    pub initial_balance: u64,
+    pub buy_in_model: BuyInModel,
+    pub max_boards: u8,
+    pub rabbit_hunt: bool,
+    #[serde(default)]
+    pub action_clock: u64, // seconds to act, 0 without a clock
+    #[serde(default)]
+    pub training: bool, // players see hints about their own hand
+    #[serde(default)]
+    pub hand_pause: u64, // seconds between hands, 0 for none
+    #[serde(default)]
+    pub level_duration: u64, // seconds before the blinds double, 0 for fixed blinds
+}
+
+impl TableRecord {
+    // a freezeout on a single board, without a clock, pauses or blind levels
+    pub fn new_table(
+        max_players: usize,
+        small_blind: u64,
+        big_blind: u64,
+        initial_balance: u64,
+    ) -> TableRecord {
+        TableRecord {
+            max_players,
+            small_blind,
+            big_blind,
+            initial_balance,
+            buy_in_model: BuyInModel::Freezeout,
+            max_boards: 1,
+            rabbit_hunt: false,
+            action_clock: 0,
+            training: false,
+            hand_pause: 0,
+            level_duration: 0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct SeatRecord {
+    pub seat_index: u8,
+    pub nickname: String,
+    #[serde(skip)]
+    pub player_id: Uuid, // kept from the viewers, it is what a session plays with
+    pub stack: u64,               // balance before the blinds were posted
+    pub cards: Option<[Card; 2]>, // None for players sitting the hand out or hidden from the viewer
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq)]
+pub struct ActionRecord {
+    pub street: GamePhase,
+    pub seat_index: u8,
+    pub action: HandAction,
+    pub amount: u64,  // chips put in by the action
+    pub balance: u64, // player's balance afterwards
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ShowdownRecord {
+    pub seat_index: u8,
+    pub cards: [Card; 2],
+    pub hands: Vec<String>, // best hand on every board
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct HandHistory {
+    pub hand_number: u32,
+    pub started_at: u64, // unix timestamp in seconds
+    pub table: TableRecord,
+    pub dealer_seat: usize,
+    pub small_blind_seat: usize,
+    pub big_blind_seat: usize,
+    pub seats: Vec<SeatRecord>,
+    pub actions: Vec<ActionRecord>,
+    pub boards: Vec<[Option<Card>; 5]>, // only the cards that were dealt
+    pub showdown: Vec<ShowdownRecord>,
+    pub pots: Vec<PotResult>,
+    pub deck: Option<Vec<Card>>, // full deck order, kept for replays and hidden from players
+}
+
+impl HandHistory {
+    pub fn record_action(
+        &mut self,
+        street: GamePhase,
+        seat_index: u8,
+        action: HandAction,
+        amount: u64,
+        balance: u64,
+    ) {
+        self.actions.push(ActionRecord {
+            street,
+            seat_index,
+            action,
+            amount,
+            balance,
+        });
+    }
+
+    // Copy of the history as seen from `viewer_seat`: own hole cards and the ones shown down stay visible.
+    pub fn visible_to(&self, viewer_seat: Option<usize>) -> HandHistory {
+        let mut history = self.clone();
+        history.deck = None;
+        for seat in history.seats.iter_mut() {
+            let shown = self
+                .showdown
+                .iter()
+                .any(|record| record.seat_index == seat.seat_index);
+            if !shown && viewer_seat != Some(seat.seat_index as usize) {
+                seat.cards = None;
+            }
+        }
+        history
+    }
+
+    // nicknames are not unique over time, the player id of the seat is
+    pub fn seat_of(&self, player_id: Uuid) -> Option<usize> {
+        self.seats
+            .iter()
+            .find(|seat| seat.player_id == player_id)
+            .map(|seat| seat.seat_index as usize)
+    }
+
+    // whether another recording of the hand played out the same way
+    pub fn same_outcome(&self, other: &HandHistory) -> bool {
+        self.actions == other.actions && self.boards == other.boards && self.pots == other.pots
+    }
+}
@@ -0,0 +1,129 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Append-only record of every chip movement at a table. Entries that bring chips to the table
+// or take them away change the table total, the rest only move chips between a player's stack,
+// their bet in the current street and the pot.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum LedgerEntryKind {
+    BuyIn,   // chips received when joining the table
+    TopUp,   // cash table top-up between hands
+    Rebuy,   // tournament rebuy during the rebuy period
+    AddOn,   // single tournament add-on at the end of the rebuy period
+    Bet,     // stack -> current street bet (blinds, bets, calls, all-ins)
+    Return,  // uncalled part of a bet -> stack
+    Collect, // current street bet -> pot
+    Win,     // pot -> stack
+    CashOut, // chips taken from the table when leaving
+}
+
+impl LedgerEntryKind {
+    pub const fn adds_chips(self) -> bool {
+        use LedgerEntryKind::*;
+        matches!(self, BuyIn | TopUp | Rebuy | AddOn)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Debug)]
+pub struct LedgerEntry {
+    pub hand_number: u32, // hand the movement belongs to, 0 before the first hand
+    pub seat_index: u8,
+    #[serde(skip)]
+    pub player_id: Uuid, // seats change hands, kept from the viewers like every player id
+    pub kind: LedgerEntryKind,
+    pub amount: u64,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct ChipAlert {
+    pub hand_number: u32,
+    pub expected: u64, // chips brought in minus chips cashed out
+    pub actual: u64,   // chips found in stacks, bets and the pot
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct ChipLedger {
+    entries: Vec<LedgerEntry>,
+    alerts: Vec<ChipAlert>,
+    #[serde(skip)]
+    seated: HashMap<u8, Uuid>, // player at every seat, entries are recorded for them
+}
+
+impl ChipLedger {
+    pub fn new_ledger() -> ChipLedger {
+        ChipLedger {
+            entries: Vec::new(),
+            alerts: Vec::new(),
+            seated: HashMap::new(),
+        }
+    }
+
+    // the player joining the seat, before the buy-in is recorded
+    pub fn seat(&mut self, seat_index: u8, player_id: Uuid) {
+        self.seated.insert(seat_index, player_id);
+    }
+
+    pub fn record(&mut self, hand_number: u32, seat_index: u8, kind: LedgerEntryKind, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+        self.entries.push(LedgerEntry {
+            hand_number,
+            seat_index,
+            player_id: self.seated.get(&seat_index).copied().unwrap_or_default(),
+            kind,
+            amount,
+        });
+    }
+
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    pub fn entries_for_hand(&self, hand_number: u32) -> Vec<LedgerEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.hand_number == hand_number)
+            .copied()
+            .collect()
+    }
+
+    // entries of the player only, not of whoever sat at their seat before
+    pub fn count(&self, player_id: Uuid, kind: LedgerEntryKind) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.player_id == player_id && entry.kind == kind)
+            .count()
+    }
+
+    // chips the table should hold: buy-ins, top-ups, rebuys and add-ons minus cash-outs
+    pub fn expected_table_chips(&self) -> u64 {
+        let brought_in: u64 = self
+            .entries
+            .iter()
+            .filter(|entry| entry.kind.adds_chips())
+            .map(|entry| entry.amount)
+            .sum();
+        let cashed_out: u64 = self
+            .entries
+            .iter()
+            .filter(|entry| entry.kind == LedgerEntryKind::CashOut)
+            .map(|entry| entry.amount)
+            .sum();
+        brought_in - cashed_out
+    }
+
+    pub fn raise_alert(&mut self, alert: ChipAlert) {
+        error!(
+            "chip conservation violated in hand {}, expected {} chips on the table, found {}",
+            alert.hand_number, alert.expected, alert.actual
+        );
+        self.alerts.push(alert);
+    }
+
+    pub fn alerts(&self) -> &[ChipAlert] {
+        &self.alerts
+    }
+}
@@ -1,9 +1,22 @@
+pub mod accounts;
+pub mod bot;
+pub mod buy_in;
+pub mod deck;
+pub mod equity;
+pub mod events;
+pub mod fairness;
 pub mod games_manager;
+pub mod hints;
+pub mod history;
 pub mod game;
+pub mod ledger;
 pub mod player;
-
-mod poker {
-    fn get_version() -> f32 {
-        0.1
-    }
-}
\ No newline at end of file
+pub mod pokerstars;
+pub mod pot;
+pub mod range;
+pub mod reaper;
+pub mod replay;
+pub mod runout;
+pub mod stats;
+pub mod storage;
+pub mod table;
@@ -1,4 +1,6 @@
+use crate::poker::bot::BotKind;
 use crate::poker::game::{Card, Color, Rank};
+use log::debug;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy)]
@@ -49,18 +51,30 @@ impl Player {
             PlayerAction::Call => self.call(amount), // amount is the bet that is to be called
             PlayerAction::Check => self.check(amount), // amount is previous bet that is to be equalized
             PlayerAction::Fold => self.fold(),
+            PlayerAction::RunIt => Err("not a betting action"),
         }
     }
 
     pub fn take_card(&mut self, which: usize, card: &Card) {
         // which is index, 0 or 1
         self.cards[which] = card.clone();
-        println!("player at seat {} took card {:?} into idx {}", self.seat_index, card, which);
+        debug!("player at seat {} took card {:?} into idx {}", self.seat_index, card, which);
     }
 
     pub fn collect_win(&mut self, amount: u64) {
         self.balance += amount;
-        println!("player at seat {} took winnings {}", self.seat_index, amount);
+        debug!("player at seat {} took winnings {}", self.seat_index, amount);
+    }
+
+    pub fn add_chips(&mut self, amount: u64) {
+        self.balance += amount;
+        debug!("player at seat {} added {} chips", self.seat_index, amount);
+    }
+
+    pub fn return_bet(&mut self, amount: u64) {
+        self.current_bet -= amount;
+        self.balance += amount;
+        debug!("player at seat {} got back uncalled {}", self.seat_index, amount);
     }
 
     pub fn reset_for_next_round(&mut self) {
@@ -71,11 +85,11 @@ impl Player {
     fn check(&mut self, to_check: u64) -> Result<u64, &str> {
         // when betting more money is not needed
         if self.current_bet < to_check {
-            println!("player at seat {} failed to check with {}", self.seat_index, to_check);
+            debug!("player at seat {} failed to check with {}", self.seat_index, to_check);
             return Err("bet amount too small to check");
         }
         self.state = PlayerState::Check;
-        println!("player at seat {} checked sum of {} with {}", self.seat_index, to_check, self.current_bet);
+        debug!("player at seat {} checked sum of {} with {}", self.seat_index, to_check, self.current_bet);
         Ok(self.current_bet)
     }
 
@@ -84,7 +98,7 @@ impl Player {
         let amount = to_call_total - self.current_bet;
 
         if amount > self.balance {
-            println!("player at seat {} failed to call with {}", self.seat_index, to_call_total);
+            debug!("player at seat {} failed to call with {}", self.seat_index, to_call_total);
             return Err("insufficient balance");
         }
 
@@ -92,11 +106,11 @@ impl Player {
         self.current_bet += amount;
 
         self.state = PlayerState::Call;
-        println!("player at seat {} called with {}", self.seat_index, to_call_total);
+        debug!("player at seat {} called with {}", self.seat_index, to_call_total);
 
         if self.balance == 0 {
             self.state = PlayerState::AllIn;
-            println!("player at seat {} called but thats all in", self.seat_index);
+            debug!("player at seat {} called but thats all in", self.seat_index);
         }
 
         Ok(self.current_bet)
@@ -105,17 +119,17 @@ impl Player {
     fn bet(&mut self, amount: u64) -> Result<u64, &str> {
         // also as rise (bet is when you are going first, rise if you aren't first)
         if amount > self.balance {
-            println!("player at seat {} failed to bet with {}", self.seat_index, amount);
+            debug!("player at seat {} failed to bet with {}", self.seat_index, amount);
             return Err("insufficient balance");
         }
         self.balance -= amount;
         self.current_bet += amount;
         self.state = PlayerState::Active;
-        println!("player at seat {} bet successfully with {}", self.seat_index, amount);
+        debug!("player at seat {} bet successfully with {}", self.seat_index, amount);
 
         if self.balance == 0 {
             self.state = PlayerState::AllIn;
-            println!("player at seat {} bet but thats all in", self.seat_index);
+            debug!("player at seat {} bet but thats all in", self.seat_index);
         }
 
         Ok(self.current_bet)
@@ -125,14 +139,14 @@ impl Player {
         self.current_bet += self.balance;
         self.balance = 0;
         self.state = PlayerState::AllIn;
-        println!("player at seat {} all in", self.seat_index);
+        debug!("player at seat {} all in", self.seat_index);
         Ok(self.current_bet)
     }
 
     fn fold(&mut self) -> Result<u64, &str> {
         // return type for completness sake
         self.state = PlayerState::Folded;
-        println!("player at seat {} folded", self.seat_index);
+        debug!("player at seat {} folded", self.seat_index);
         Ok(self.balance)
     }
 
@@ -144,14 +158,14 @@ impl Player {
             _ => ()
         };
         self.state = PlayerState::Active;
-        println!("setting player state at seat {} as active, {:?}", self.seat_index, self.state);
+        debug!("setting player state at seat {} as active, {:?}", self.seat_index, self.state);
         self
     }
 
     pub fn collect_bet(&mut self) -> Result<u64, &str> {
         self.total_bet += self.current_bet;
         self.current_bet = 0;
-        println!("player at seat {} collected his {} bet into the main pool", self.seat_index, self.total_bet);
+        debug!("player at seat {} collected his {} bet into the main pool", self.seat_index, self.total_bet);
         Ok(self.total_bet)
     }
 }
@@ -163,6 +177,7 @@ pub struct PlayerData {
     pub state: PlayerState,
     pub bet_amount: u64,
     pub nickname: String,
+    pub bot: Option<BotKind>, // None for human players
 }
 
 #[derive(PartialEq, Clone, Copy, Serialize, Debug)]
@@ -177,11 +192,12 @@ pub enum PlayerState {
     Left,     // when player left the game
 }
 
-#[derive(PartialEq, Deserialize, Clone, Copy, Serialize)]
+#[derive(PartialEq, Deserialize, Clone, Copy, Serialize, Debug)]
 pub enum PlayerAction {
     Check,
     Call,
     Bet,
     Fold,
     AllIn,
+    RunIt, // choose how many times to run out an all-in hand, amount is the number of boards
 }
@@ -0,0 +1,594 @@
+use crate::poker::game::{Card, GamePhase};
+use crate::poker::games_manager::PotResult;
+use crate::poker::history::{HandAction, HandHistory};
+use std::collections::HashMap;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// Renders recorded hands in the PokerStars hand history text format understood by tracking
+// tools. Hole cards are written only for `hero_seat`, everything else comes from showdowns.
+pub fn export_hands(game_id: Uuid, histories: &[HandHistory], hero_seat: Option<usize>) -> String {
+    let mut out = String::new();
+    for history in histories {
+        out.push_str(&export_hand(game_id, history, hero_seat));
+        out.push_str("\n\n\n");
+    }
+    out
+}
+
+pub fn export_hand(game_id: Uuid, history: &HandHistory, hero_seat: Option<usize>) -> String {
+    let mut out = String::new();
+    let names: HashMap<u8, &str> = history
+        .seats
+        .iter()
+        .map(|seat| (seat.seat_index, seat.nickname.as_str()))
+        .collect();
+    let name = |seat_index: u8| names.get(&seat_index).copied().unwrap_or("unknown");
+
+    let _ = writeln!(
+        out,
+        "PokerStars Hand #{}: Hold'em No Limit ({}/{}) - {}",
+        hand_id(game_id, history.hand_number),
+        history.table.small_blind,
+        history.table.big_blind,
+        eastern_time(history.started_at)
+    );
+    let _ = writeln!(
+        out,
+        "Table '{}' {}-max Seat #{} is the button",
+        table_name(game_id),
+        history.table.max_players,
+        history.dealer_seat + 1
+    );
+    for seat in &history.seats {
+        let sitting_out = match seat.stack {
+            0 => " is sitting out",
+            _ => "",
+        };
+        let _ = writeln!(
+            out,
+            "Seat {}: {} ({} in chips){}",
+            seat.seat_index + 1,
+            seat.nickname,
+            seat.stack,
+            sitting_out
+        );
+    }
+
+    let boards = &history.boards;
+    let dealt = boards
+        .first()
+        .map(|board| board.iter().flatten().count())
+        .unwrap_or(0);
+    // cards every board has in common, dealt before the hand was run more than once
+    let shared = (0..dealt)
+        .take_while(|idx| boards.iter().all(|board| board[*idx] == boards[0][*idx]))
+        .count();
+
+    let mut street = GamePhase::PreFlop;
+    let mut street_bets: HashMap<u8, u64> = HashMap::new();
+    let mut max_bet = 0;
+    let mut hole_cards_written = false;
+    for action in &history.actions {
+        let blind = matches!(action.action, HandAction::SmallBlind | HandAction::BigBlind);
+        if !blind && !hole_cards_written {
+            write_hole_cards(&mut out, history, hero_seat);
+            hole_cards_written = true;
+        }
+        while street_index(street) < street_index(action.street) {
+            street = next_street(street);
+            write_street(&mut out, street, boards, shared);
+            street_bets.clear();
+            max_bet = 0;
+        }
+
+        let player = name(action.seat_index);
+        let all_in = match action.balance == 0 && action.amount > 0 {
+            true => " and is all-in",
+            false => "",
+        };
+        let bet_before = street_bets.get(&action.seat_index).copied().unwrap_or(0);
+        let bet_after = bet_before + action.amount;
+        match action.action {
+            HandAction::SmallBlind => {
+                let _ = writeln!(
+                    out,
+                    "{}: posts small blind {}{}",
+                    player, action.amount, all_in
+                );
+            }
+            HandAction::BigBlind => {
+                let _ = writeln!(
+                    out,
+                    "{}: posts big blind {}{}",
+                    player, action.amount, all_in
+                );
+            }
+            HandAction::Fold => {
+                let _ = writeln!(out, "{}: folds", player);
+            }
+            HandAction::RunIt => (),
+            HandAction::UncalledBetReturned => {
+                let _ = writeln!(
+                    out,
+                    "Uncalled bet ({}) returned to {}",
+                    action.amount, player
+                );
+                street_bets.insert(action.seat_index, bet_before - action.amount);
+                max_bet = street_bets.values().copied().max().unwrap_or(0);
+                continue;
+            }
+            _ if action.amount == 0 => {
+                let _ = writeln!(out, "{}: checks", player);
+            }
+            _ if bet_after <= max_bet => {
+                let _ = writeln!(out, "{}: calls {}{}", player, action.amount, all_in);
+            }
+            _ if max_bet == 0 => {
+                let _ = writeln!(out, "{}: bets {}{}", player, action.amount, all_in);
+            }
+            _ => {
+                let _ = writeln!(
+                    out,
+                    "{}: raises {} to {}{}",
+                    player,
+                    bet_after - max_bet,
+                    bet_after,
+                    all_in
+                );
+            }
+        }
+        street_bets.insert(action.seat_index, bet_after);
+        max_bet = max_bet.max(bet_after);
+    }
+    if !hole_cards_written {
+        write_hole_cards(&mut out, history, hero_seat);
+    }
+    // the rest of the board was dealt without any betting
+    while street_index(street) < dealt_street_index(dealt) {
+        street = next_street(street);
+        write_street(&mut out, street, boards, shared);
+    }
+
+    if !history.showdown.is_empty() {
+        let _ = writeln!(out, "*** SHOW DOWN ***");
+        for record in &history.showdown {
+            let _ = writeln!(
+                out,
+                "{}: shows [{}] ({})",
+                name(record.seat_index),
+                cards_text(&record.cards),
+                record.hands.join(", ")
+            );
+        }
+    }
+
+    // winnings of every seat, pots are listed per board
+    let mut won: HashMap<usize, u64> = HashMap::new();
+    for (pot, pot_name) in history.pots.iter().zip(pot_names(&history.pots)) {
+        for (winner_seat, amount) in pot_shares(pot.amount, &pot.winners) {
+            *won.entry(winner_seat).or_insert(0) += amount;
+            let _ = writeln!(
+                out,
+                "{} collected {} from {}",
+                name(winner_seat as u8),
+                amount,
+                pot_name
+            );
+        }
+    }
+
+    let _ = writeln!(out, "*** SUMMARY ***");
+    let total: u64 = history.pots.iter().map(|pot| pot.amount).sum();
+    let _ = writeln!(out, "Total pot {} | Rake 0", total);
+    match boards.len() {
+        0 => (),
+        1 => {
+            if dealt > 0 {
+                let _ = writeln!(out, "Board [{}]", board_text(&boards[0]));
+            }
+        }
+        count => {
+            let times = match count {
+                2 => "twice",
+                _ => "three times",
+            };
+            let _ = writeln!(out, "Hand was run {}", times);
+            for (idx, board) in boards.iter().enumerate() {
+                let _ = writeln!(out, "{} Board [{}]", ordinal(idx), board_text(board));
+            }
+        }
+    }
+    for seat in &history.seats {
+        if seat.stack == 0 {
+            continue;
+        }
+        let position = if seat.seat_index as usize == history.dealer_seat {
+            " (button)"
+        } else if seat.seat_index as usize == history.small_blind_seat {
+            " (small blind)"
+        } else if seat.seat_index as usize == history.big_blind_seat {
+            " (big blind)"
+        } else {
+            ""
+        };
+        let fold = history.actions.iter().find(|action| {
+            action.seat_index == seat.seat_index && action.action == HandAction::Fold
+        });
+        let shown = history
+            .showdown
+            .iter()
+            .find(|record| record.seat_index == seat.seat_index);
+        let winnings = won.get(&(seat.seat_index as usize)).copied();
+
+        let outcome = match (fold, shown, winnings) {
+            (Some(fold), _, _) => {
+                let bet = history.actions.iter().any(|action| {
+                    action.seat_index == seat.seat_index
+                        && action.amount > 0
+                        && action.action != HandAction::RunIt
+                });
+                match (fold.street, bet) {
+                    (GamePhase::PreFlop, false) => "folded before Flop (didn't bet)".to_string(),
+                    (GamePhase::PreFlop, true) => "folded before Flop".to_string(),
+                    (street, _) => format!("folded on the {}", street_name(street)),
+                }
+            }
+            (None, Some(record), Some(amount)) => format!(
+                "showed [{}] and won ({}) with {}",
+                cards_text(&record.cards),
+                amount,
+                record.hands.join(", ")
+            ),
+            (None, Some(record), None) => format!(
+                "showed [{}] and lost with {}",
+                cards_text(&record.cards),
+                record.hands.join(", ")
+            ),
+            (None, None, Some(amount)) => format!("collected ({})", amount),
+            (None, None, None) => "mucked".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "Seat {}: {}{} {}",
+            seat.seat_index + 1,
+            seat.nickname,
+            position,
+            outcome
+        );
+    }
+
+    out
+}
+
+fn write_hole_cards(out: &mut String, history: &HandHistory, hero_seat: Option<usize>) {
+    let _ = writeln!(out, "*** HOLE CARDS ***");
+    let hero = history
+        .seats
+        .iter()
+        .find(|seat| Some(seat.seat_index as usize) == hero_seat);
+    if let Some(seat) = hero {
+        if let Some(cards) = seat.cards {
+            let _ = writeln!(out, "Dealt to {} [{}]", seat.nickname, cards_text(&cards));
+        }
+    }
+}
+
+fn write_street(out: &mut String, street: GamePhase, boards: &[[Option<Card>; 5]], shared: usize) {
+    let cards = dealt_street_cards(street);
+    if cards == 0 || boards.is_empty() {
+        return;
+    }
+    if cards <= shared || boards.len() == 1 {
+        let _ = writeln!(out, "{}", street_line("", street, &boards[0]));
+        return;
+    }
+    for (idx, board) in boards.iter().enumerate() {
+        let prefix = format!("{} ", ordinal(idx));
+        let _ = writeln!(out, "{}", street_line(&prefix, street, board));
+    }
+}
+
+fn street_line(prefix: &str, street: GamePhase, board: &[Option<Card>; 5]) -> String {
+    let cards: Vec<Card> = board.iter().flatten().copied().collect();
+    let name = street_name(street).to_uppercase();
+    match street {
+        GamePhase::Flop => format!("*** {}{} *** [{}]", prefix, name, cards_text(&cards[..3])),
+        _ => {
+            let shown = dealt_street_cards(street);
+            format!(
+                "*** {}{} *** [{}] [{}]",
+                prefix,
+                name,
+                cards_text(&cards[..shown - 1]),
+                cards[shown - 1].notation()
+            )
+        }
+    }
+}
+
+fn street_index(street: GamePhase) -> usize {
+    match street {
+        GamePhase::PreFlop => 0,
+        GamePhase::Flop => 1,
+        GamePhase::Turn => 2,
+        GamePhase::River => 3,
+    }
+}
+
+fn dealt_street_index(dealt: usize) -> usize {
+    match dealt {
+        0..=2 => 0,
+        3 => 1,
+        4 => 2,
+        _ => 3,
+    }
+}
+
+fn next_street(street: GamePhase) -> GamePhase {
+    match street {
+        GamePhase::PreFlop => GamePhase::Flop,
+        GamePhase::Flop => GamePhase::Turn,
+        GamePhase::Turn | GamePhase::River => GamePhase::River,
+    }
+}
+
+fn dealt_street_cards(street: GamePhase) -> usize {
+    match street {
+        GamePhase::PreFlop => 0,
+        GamePhase::Flop => 3,
+        GamePhase::Turn => 4,
+        GamePhase::River => 5,
+    }
+}
+
+fn street_name(street: GamePhase) -> &'static str {
+    match street {
+        GamePhase::PreFlop => "Pre-Flop",
+        GamePhase::Flop => "Flop",
+        GamePhase::Turn => "Turn",
+        GamePhase::River => "River",
+    }
+}
+
+fn ordinal(board_index: usize) -> &'static str {
+    match board_index {
+        0 => "FIRST",
+        1 => "SECOND",
+        _ => "THIRD",
+    }
+}
+
+// Pots come one after another, each listed board by board. A board nobody won a pot on is left
+// out, so a pot starts where the board number does not go up.
+fn pot_names(pots: &[PotResult]) -> Vec<String> {
+    let mut numbers: Vec<usize> = Vec::with_capacity(pots.len());
+    for (idx, pot) in pots.iter().enumerate() {
+        let number = match numbers.last() {
+            None => 0,
+            Some(last) if pot.board <= pots[idx - 1].board => last + 1,
+            Some(last) => *last,
+        };
+        numbers.push(number);
+    }
+    let single_pot = numbers.last().copied().unwrap_or(0) == 0;
+    numbers
+        .into_iter()
+        .map(|number| match (single_pot, number) {
+            (true, _) => "pot".to_string(),
+            (_, 0) => "main pot".to_string(),
+            (_, side) => format!("side pot-{}", side),
+        })
+        .collect()
+}
+
+// same split as Game::distribute_winnings, odd chips go to the first winners
+fn pot_shares(amount: u64, winners: &[usize]) -> Vec<(usize, u64)> {
+    if winners.is_empty() {
+        return Vec::new();
+    }
+    let share = amount / winners.len() as u64;
+    let odd_chips = (amount % winners.len() as u64) as usize;
+    winners
+        .iter()
+        .enumerate()
+        .map(|(idx, seat)| (*seat, share + (idx < odd_chips) as u64))
+        .collect()
+}
+
+fn cards_text(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(|card| card.notation())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn board_text(board: &[Option<Card>; 5]) -> String {
+    let cards: Vec<Card> = board.iter().flatten().copied().collect();
+    cards_text(&cards)
+}
+
+// hand numbers restart with every game, trackers need ids unique across games
+fn hand_id(game_id: Uuid, hand_number: u32) -> u64 {
+    let (high, _) = game_id.as_u64_pair();
+    (high % 1_000_000_000) * 1_000_000 + hand_number as u64
+}
+
+fn table_name(game_id: Uuid) -> String {
+    format!("rusty-poker {}", &game_id.simple().to_string()[..8])
+}
+
+// days since 1970-01-01 to (year, month, day), proleptic gregorian calendar
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// first sunday on or after the given day
+fn sunday_from(days: i64) -> i64 {
+    let weekday = (days + 4).rem_euclid(7); // 1970-01-01 was a thursday, 0 is sunday
+    days + (7 - weekday) % 7
+}
+
+// PokerStars stamps hands in eastern time, daylight saving from the second sunday of march
+// to the first sunday of november, switching at 2am local time
+fn eastern_time(timestamp: u64) -> String {
+    let utc = timestamp as i64;
+    let (year, _, _) = civil_from_days(utc.div_euclid(86_400));
+    let dst_start = (sunday_from(days_from_civil(year, 3, 1)) + 7) * 86_400 + 7 * 3600;
+    let dst_end = sunday_from(days_from_civil(year, 11, 1)) * 86_400 + 6 * 3600;
+    let offset = match utc >= dst_start && utc < dst_end {
+        true => 4 * 3600,
+        false => 5 * 3600,
+    };
+    let local = utc - offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    let seconds = local.rem_euclid(86_400);
+    format!(
+        "{}/{:02}/{:02} {}:{:02}:{:02} ET",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::buy_in::{BuyInKind, BuyInModel};
+    use crate::poker::deck::DeckSource;
+    use crate::poker::game::{parse_cards, Game};
+    use crate::poker::history::TableRecord;
+    use crate::poker::player::PlayerAction;
+
+    // stacks of 500, 1000 and 1500, everybody is all in before the flop
+    fn side_pot_hand() -> HandHistory {
+        let deck = parse_cards("AsAh KsKh 7c2d Qd8c3s 9h 4d").unwrap();
+        let mut game = Game::new_game(TableRecord {
+            buy_in_model: BuyInModel::Cash { max_buy_in: 2000 },
+            ..TableRecord::new_table(3, 5, 10, 500)
+        })
+        .with_deck_source(DeckSource::stacked(vec![deck]).unwrap());
+        for seat in 0..3 {
+            game.join_game(seat, &format!("player {}", seat), 0)
+                .unwrap();
+        }
+        game.buy_chips(1, BuyInKind::TopUp, 500).unwrap();
+        game.buy_chips(2, BuyInKind::TopUp, 1000).unwrap();
+        for seat in 0..3 {
+            game.set_ready(seat, true).unwrap();
+        }
+        game.player_action(0, PlayerAction::AllIn, 0);
+        game.player_action(1, PlayerAction::AllIn, 0);
+        game.player_action(2, PlayerAction::AllIn, 0);
+        let mut history = game.hand_histories()[0].clone();
+        // 2026-07-04 16:30:00 UTC
+        history.started_at = 1783182600;
+        // names of the hands are the evaluator's, the export only copies them
+        for (record, hand) in history
+            .showdown
+            .iter_mut()
+            .zip(["Aces", "Kings", "Queen high"])
+        {
+            record.hands = vec![hand.to_string()];
+        }
+        history
+    }
+
+    fn pot(board: usize) -> PotResult {
+        PotResult {
+            amount: 100,
+            board,
+            winners: vec![0],
+            eligible_seats: vec![0, 1],
+        }
+    }
+
+    #[test]
+    fn side_pots_are_exported_with_their_winners() {
+        let game_id = Uuid::parse_str("0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0").unwrap();
+        let expected = "\
+PokerStars Hand #855742840000001: Hold'em No Limit (5/10) - 2026/07/04 12:30:00 ET
+Table 'rusty-poker 0f1e2d3c' 3-max Seat #1 is the button
+Seat 1: player 0 (500 in chips)
+Seat 2: player 1 (1000 in chips)
+Seat 3: player 2 (1500 in chips)
+player 1: posts small blind 5
+player 2: posts big blind 10
+*** HOLE CARDS ***
+Dealt to player 0 [As Ah]
+player 0: raises 490 to 500 and is all-in
+player 1: raises 500 to 1000 and is all-in
+player 2: raises 500 to 1500 and is all-in
+Uncalled bet (500) returned to player 2
+*** FLOP *** [Qd 8c 3s]
+*** TURN *** [Qd 8c 3s] [9h]
+*** RIVER *** [Qd 8c 3s 9h] [4d]
+*** SHOW DOWN ***
+player 0: shows [As Ah] (Aces)
+player 1: shows [Ks Kh] (Kings)
+player 2: shows [7c 2d] (Queen high)
+player 0 collected 1500 from main pot
+player 1 collected 1000 from side pot-1
+*** SUMMARY ***
+Total pot 2500 | Rake 0
+Board [Qd 8c 3s 9h 4d]
+Seat 1: player 0 (button) showed [As Ah] and won (1500) with Aces
+Seat 2: player 1 (small blind) showed [Ks Kh] and won (1000) with Kings
+Seat 3: player 2 (big blind) showed [7c 2d] and lost with Queen high
+";
+        assert_eq!(export_hand(game_id, &side_pot_hand(), Some(0)), expected);
+    }
+
+    #[test]
+    fn pots_are_numbered_when_a_board_has_no_winner() {
+        // two boards, nobody won the main pot on the second one
+        let pots = [pot(0), pot(0), pot(1), pot(1), pot(0)];
+        assert_eq!(
+            pot_names(&pots),
+            vec![
+                "main pot",
+                "side pot-1",
+                "side pot-1",
+                "side pot-2",
+                "side pot-3"
+            ]
+        );
+        assert_eq!(pot_names(&[pot(0), pot(1)]), vec!["pot", "pot"]);
+        assert_eq!(pot_names(&[pot(1)]), vec!["pot"]);
+    }
+
+    #[test]
+    fn hands_are_stamped_in_eastern_time_across_daylight_saving() {
+        // 2am local on march 8th 2026 skips to 3am
+        assert_eq!(eastern_time(1772953199), "2026/03/08 1:59:59 ET");
+        assert_eq!(eastern_time(1772953200), "2026/03/08 3:00:00 ET");
+        // 2am local on november 1st 2026 goes back to 1am
+        assert_eq!(eastern_time(1793512799), "2026/11/01 1:59:59 ET");
+        assert_eq!(eastern_time(1793512800), "2026/11/01 1:00:00 ET");
+        // leap day, standard time
+        assert_eq!(eastern_time(1709208000), "2024/02/29 7:00:00 ET");
+    }
+}
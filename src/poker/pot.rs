@@ -0,0 +1,66 @@
+#[derive(Clone, Copy, Debug)]
+pub struct Contribution {
+    pub seat_index: usize,
+    pub amount: u64,      // chips put into the pot during the hand
+    pub contesting: bool, // false for players who folded or left
+}
+
+#[derive(Clone, Debug)]
+pub struct Pot {
+    pub amount: u64,
+    pub eligible_seats: Vec<usize>,
+}
+
+// Splits the chips of a hand into the main pot and side pots. A new pot starts at every
+// all-in level of the contesting players, only players who covered that level may win it.
+pub fn build_pots(contributions: &[Contribution]) -> Vec<Pot> {
+    let mut levels: Vec<u64> = contributions
+        .iter()
+        .filter(|contribution| contribution.contesting && contribution.amount > 0)
+        .map(|contribution| contribution.amount)
+        .collect();
+    levels.sort();
+    levels.dedup();
+
+    let mut pots: Vec<Pot> = Vec::new();
+    let mut previous_level = 0;
+    for level in levels {
+        let amount = contributions
+            .iter()
+            .map(|contribution| {
+                contribution.amount.min(level) - contribution.amount.min(previous_level)
+            })
+            .sum();
+        let eligible_seats = contributions
+            .iter()
+            .filter(|contribution| contribution.contesting && contribution.amount >= level)
+            .map(|contribution| contribution.seat_index)
+            .collect();
+        pots.push(Pot {
+            amount,
+            eligible_seats,
+        });
+        previous_level = level;
+    }
+
+    // chips above the highest contesting level come from players who folded
+    let leftover: u64 = contributions
+        .iter()
+        .map(|contribution| contribution.amount.saturating_sub(previous_level))
+        .sum();
+    if leftover > 0 {
+        match pots.last_mut() {
+            Some(pot) => pot.amount += leftover,
+            None => pots.push(Pot {
+                amount: leftover,
+                eligible_seats: contributions
+                    .iter()
+                    .filter(|contribution| contribution.contesting)
+                    .map(|contribution| contribution.seat_index)
+                    .collect(),
+            }),
+        }
+    }
+
+    pots
+}
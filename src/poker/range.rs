@@ -0,0 +1,285 @@
+use crate::poker::equity::{equity, EquityResult, Holding};
+use crate::poker::game::{parse_cards, Card, Color, Rank};
+use poker::Evaluator;
+use rand::Rng;
+use std::str::FromStr;
+
+const COLORS: [Color; 4] = [Color::Spades, Color::Hearts, Color::Diamonds, Color::Clubs];
+
+// Two card hands written in range notation, "22+, A2s+, KTo+, QJs, AsKd". Every hand is kept once,
+// its higher card first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range {
+    pub combos: Vec<[Card; 2]>,
+}
+
+// "AK" stands for every ace king, "AKs" for the suited ones and "AKo" for the others
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct HandClass {
+    high: u8, // ranks from 0 for a two to 12 for an ace
+    low: u8,
+    suited: Option<bool>,
+}
+
+// the deck starts with the spades from two to ace
+fn rank_at(value: u8) -> Rank {
+    Card::ordered_deck()[value as usize].rank()
+}
+
+impl FromStr for HandClass {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<HandClass, &'static str> {
+        let symbols: Vec<String> = s.chars().map(|c| c.to_string()).collect();
+        let (ranks, suited) = match symbols.len() {
+            2 => (&symbols[..], None),
+            3 => match symbols[2].as_str() {
+                "s" | "S" => (&symbols[..2], Some(true)),
+                "o" | "O" => (&symbols[..2], Some(false)),
+                _ => return Err("a hand class ends with s for suited or o for offsuit"),
+            },
+            _ => return Err("invalid hand class"),
+        };
+        let first = ranks[0].parse::<Rank>()? as u8;
+        let second = ranks[1].parse::<Rank>()? as u8;
+        if first == second && suited.is_some() {
+            return Err("pairs are neither suited nor offsuit");
+        }
+        Ok(HandClass {
+            high: first.max(second),
+            low: first.min(second),
+            suited,
+        })
+    }
+}
+
+impl HandClass {
+    fn combos(&self) -> Vec<[Card; 2]> {
+        let (high, low) = (rank_at(self.high), rank_at(self.low));
+        let mut combos = Vec::new();
+        for (first, high_color) in COLORS.iter().enumerate() {
+            for (second, low_color) in COLORS.iter().enumerate() {
+                let keep = match (self.high == self.low, self.suited) {
+                    (true, _) => first < second,
+                    (false, Some(true)) => first == second,
+                    (false, Some(false)) => first != second,
+                    (false, None) => true,
+                };
+                if keep {
+                    combos.push([Card::new(*high_color, high), Card::new(*low_color, low)]);
+                }
+            }
+        }
+        combos
+    }
+
+    // "22-55" or "A5s-A2s", every class between the two, in either order
+    fn up_to(self, last: HandClass) -> Result<Vec<HandClass>, &'static str> {
+        let pairs = self.high == self.low && last.high == last.low;
+        if pairs {
+            return Ok((self.high.min(last.high)..=self.high.max(last.high))
+                .map(|rank| HandClass {
+                    high: rank,
+                    low: rank,
+                    suited: None,
+                })
+                .collect());
+        }
+        if self.high != last.high || self.suited != last.suited || self.high == self.low {
+            return Err("a range keeps its first card and suits, only the kicker changes");
+        }
+        Ok((self.low.min(last.low)..=self.low.max(last.low))
+            .map(|low| HandClass { low, ..self })
+            .collect())
+    }
+
+    // "22+" for every pair from deuces up, "A2s+" for every kicker up to the one below the ace
+    fn and_better(self) -> Vec<HandClass> {
+        match self.high == self.low {
+            true => (self.high..=12)
+                .map(|rank| HandClass {
+                    high: rank,
+                    low: rank,
+                    suited: None,
+                })
+                .collect(),
+            false => (self.low..self.high)
+                .map(|low| HandClass { low, ..self })
+                .collect(),
+        }
+    }
+}
+
+fn parse_part(part: &str) -> Result<Vec<[Card; 2]>, &'static str> {
+    if let Ok(cards) = parse_cards(part) {
+        return match cards.len() {
+            2 if cards[0] != cards[1] => Ok(vec![[cards[0], cards[1]]]),
+            _ => Err("a hand takes two different cards"),
+        };
+    }
+    let classes = if let Some((first, last)) = part.split_once('-') {
+        first.parse::<HandClass>()?.up_to(last.parse()?)?
+    } else if let Some(first) = part.strip_suffix('+') {
+        first.parse::<HandClass>()?.and_better()
+    } else {
+        vec![part.parse()?]
+    };
+    Ok(classes.iter().flat_map(|class| class.combos()).collect())
+}
+
+impl FromStr for Range {
+    type Err = &'static str;
+
+    // parts are separated by commas or spaces
+    fn from_str(s: &str) -> Result<Range, &'static str> {
+        let mut range = Range { combos: Vec::new() };
+        for part in s.split(|c: char| c == ',' || c.is_whitespace()) {
+            if part.is_empty() {
+                continue;
+            }
+            for combo in parse_part(part)? {
+                range.add(combo);
+            }
+        }
+        if range.combos.is_empty() {
+            return Err("empty range");
+        }
+        Ok(range)
+    }
+}
+
+impl Range {
+    fn add(&mut self, combo: [Card; 2]) {
+        let combo = match combo[0].rank() as u8 >= combo[1].rank() as u8 {
+            true => combo,
+            false => [combo[1], combo[0]],
+        };
+        let known = |other: &[Card; 2]| {
+            (other[0] == combo[0] && other[1] == combo[1])
+                || (other[0] == combo[1] && other[1] == combo[0])
+        };
+        if !self.combos.iter().any(known) {
+            self.combos.push(combo);
+        }
+    }
+
+    // the hands still possible once the known cards are out of the deck
+    pub fn without(&self, known: &[Card]) -> Range {
+        Range {
+            combos: self
+                .combos
+                .iter()
+                .filter(|combo| !combo.iter().any(|card| known.contains(card)))
+                .copied()
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.combos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.combos.is_empty()
+    }
+
+    // every hand as "AsKd"
+    pub fn notation(&self) -> Vec<String> {
+        self.combos
+            .iter()
+            .map(|combo| format!("{}{}", combo[0], combo[1]))
+            .collect()
+    }
+}
+
+// Chances of every range against the others, hands sharing a card with the board, the dead cards
+// or each other are never dealt together
+pub fn range_equity(
+    evaluator: &Evaluator,
+    ranges: &[Range],
+    board: &[Card],
+    dead: &[Card],
+    rng: &mut impl Rng,
+) -> Result<EquityResult, &'static str> {
+    let holdings: Vec<Holding> = ranges
+        .iter()
+        .map(|range| Holding::Combos(range.combos.clone()))
+        .collect();
+    equity(evaluator, &holdings, board, dead, rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combos(range: &str) -> usize {
+        range.parse::<Range>().unwrap().len()
+    }
+
+    #[test]
+    fn hand_classes_parse_in_any_rank_order() {
+        let class: HandClass = "KAs".parse().unwrap();
+        assert_eq!(class, "AKs".parse().unwrap());
+        assert_eq!((class.high, class.low, class.suited), (12, 11, Some(true)));
+        assert_eq!("T9".parse::<HandClass>().unwrap().suited, None);
+        assert!("AAs".parse::<HandClass>().is_err());
+        assert!("AKx".parse::<HandClass>().is_err());
+        assert!("A".parse::<HandClass>().is_err());
+    }
+
+    #[test]
+    fn plus_takes_every_better_class() {
+        assert_eq!(combos("22+"), 78);
+        assert_eq!(combos("TT+"), 30);
+        assert_eq!(combos("A2s+"), 48);
+        assert_eq!(combos("KTo+"), 36);
+        assert_eq!(combos("KT+"), 48);
+    }
+
+    #[test]
+    fn dash_takes_every_class_in_between() {
+        assert_eq!(combos("AKs-A2s"), 48);
+        assert_eq!(combos("A2s-AKs"), 48);
+        assert_eq!(combos("55-22"), 24);
+        let first: HandClass = "AKs".parse().unwrap();
+        assert!(first.up_to("QJs".parse().unwrap()).is_err());
+        assert!(first.up_to("A2o".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn hands_are_kept_once() {
+        assert_eq!(combos("AK, AKs"), 16);
+        assert_eq!(combos("AsKd KdAs"), 1);
+        assert_eq!("KdAs".parse::<Range>().unwrap().notation(), vec!["AsKd"]);
+        assert!("AsAs".parse::<Range>().is_err());
+        assert!(", ".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn known_cards_remove_the_hands_holding_them() {
+        let aces: Range = "AA".parse().unwrap();
+        assert_eq!(aces.without(&parse_cards("As").unwrap()).len(), 3);
+        assert_eq!(aces.without(&parse_cards("AsAh").unwrap()).len(), 1);
+        let suited: Range = "AKs".parse().unwrap();
+        let left = suited.without(&parse_cards("AsKh2c").unwrap());
+        assert_eq!(left.notation(), vec!["AdKd", "AcKc"]);
+    }
+
+    #[test]
+    fn holdings_take_cards_before_ranges() {
+        assert_eq!(
+            "AsKd".parse::<Holding>().unwrap(),
+            Holding::Cards([Card::from_str("As").unwrap(), Card::from_str("Kd").unwrap()])
+        );
+        match "AsKd, AhKh".parse::<Holding>().unwrap() {
+            Holding::Combos(combos) => assert_eq!(combos.len(), 2),
+            other => panic!("expected two hands, got {:?}", other),
+        }
+        match "AK".parse::<Holding>().unwrap() {
+            Holding::Combos(combos) => assert_eq!(combos.len(), 16),
+            other => panic!("expected a range, got {:?}", other),
+        }
+        assert_eq!(" Random ".parse::<Holding>().unwrap(), Holding::Random);
+        assert!("AsKdQh".parse::<Holding>().is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::poker::game::GamePlayState;
+use crate::poker::games_manager::GameData;
+use crate::poker::history::HandHistory;
+use crate::poker::ledger::LedgerEntry;
+use crate::poker::player::PlayerData;
+use serde::Serialize;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq)]
+pub enum ReapReason {
+    NeverStarted,
+    Idle,
+    Ended,
+}
+
+// How long a game may go without any change before it is archived, in seconds, 0 keeps it forever
+#[derive(Clone, Copy, Debug)]
+pub struct ReaperConfig {
+    pub not_started_ttl: u64,
+    pub idle_ttl: u64, // started games nobody plays anymore
+    pub ended_ttl: u64,
+    pub interval: u64, // seconds between two sweeps
+}
+
+impl ReaperConfig {
+    // RUSTY_POKER_TTL_NOT_STARTED, RUSTY_POKER_TTL_IDLE, RUSTY_POKER_TTL_ENDED and
+    // RUSTY_POKER_REAPER_INTERVAL override the defaults
+    pub fn from_env() -> ReaperConfig {
+        let seconds = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        ReaperConfig {
+            not_started_ttl: seconds("RUSTY_POKER_TTL_NOT_STARTED", 3600),
+            idle_ttl: seconds("RUSTY_POKER_TTL_IDLE", 7200),
+            ended_ttl: seconds("RUSTY_POKER_TTL_ENDED", 1800),
+            interval: seconds("RUSTY_POKER_REAPER_INTERVAL", 60).max(1),
+        }
+    }
+
+    pub fn reason(&self, game_state: GamePlayState, idle_for: u64) -> Option<ReapReason> {
+        let (ttl, reason) = match game_state {
+            GamePlayState::NotStarted => (self.not_started_ttl, ReapReason::NeverStarted),
+            GamePlayState::Started => (self.idle_ttl, ReapReason::Idle),
+            GamePlayState::Ended => (self.ended_ttl, ReapReason::Ended),
+        };
+        match ttl > 0 && idle_for >= ttl {
+            true => Some(reason),
+            false => None,
+        }
+    }
+}
+
+// What is kept of a game once it is removed, its event log is archived next to it
+#[derive(Serialize)]
+pub struct GameArchive {
+    pub game_id: Uuid,
+    pub archived_at: u64,
+    pub reason: ReapReason,
+    pub game: GameData,
+    pub players: Vec<PlayerData>, // final stacks
+    pub hands: Vec<HandHistory>,
+    pub ledger: Vec<LedgerEntry>,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: ReaperConfig = ReaperConfig {
+        not_started_ttl: 3600,
+        idle_ttl: 7200,
+        ended_ttl: 1800,
+        interval: 60,
+    };
+
+    #[test]
+    fn games_are_reaped_once_idle_for_the_ttl_of_their_state() {
+        assert_eq!(CONFIG.reason(GamePlayState::NotStarted, 3599), None);
+        assert_eq!(
+            CONFIG.reason(GamePlayState::NotStarted, 3600),
+            Some(ReapReason::NeverStarted)
+        );
+        assert_eq!(CONFIG.reason(GamePlayState::Started, 3600), None);
+        assert_eq!(
+            CONFIG.reason(GamePlayState::Started, 7200),
+            Some(ReapReason::Idle)
+        );
+        assert_eq!(CONFIG.reason(GamePlayState::Ended, 1799), None);
+        assert_eq!(
+            CONFIG.reason(GamePlayState::Ended, 1800),
+            Some(ReapReason::Ended)
+        );
+    }
+
+    #[test]
+    fn a_ttl_of_zero_keeps_games_forever() {
+        let config = ReaperConfig {
+            idle_ttl: 0,
+            ..CONFIG
+        };
+        assert_eq!(config.reason(GamePlayState::Started, u64::MAX), None);
+        assert_eq!(config.reason(GamePlayState::Started, 0), None);
+        // the other states keep their ttl
+        assert_eq!(
+            config.reason(GamePlayState::Ended, 1800),
+            Some(ReapReason::Ended)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::poker::game::Game;
+use crate::poker::games_manager::GameState;
+use crate::poker::history::{ActionRecord, HandHistory};
+use log::warn;
+use poker::Evaluator;
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Serialize)]
+pub struct ReplayStep {
+    pub step: usize,
+    pub steps: usize, // player decisions in the hand, the last step ends it
+    pub action: Option<ActionRecord>, // decision that led to this step, None for the deal
+    pub state: GameState,
+    pub reproduced: Option<bool>, // set on the last step, whether the replay matches the record
+}
+
+// Table state after the first `step` player decisions of a recorded hand. Step 0 is the deal
+// with the blinds posted, blinds and returned bets are left to the engine.
+pub fn replay_hand(
+    history: &HandHistory,
+    step: usize,
+    viewer_seat: Option<usize>,
+    evaluator: Arc<Evaluator>,
+) -> Result<ReplayStep, &'static str> {
+    let decisions: Vec<&ActionRecord> = history
+        .actions
+        .iter()
+        .filter(|record| record.action.to_player_action().is_some())
+        .collect();
+    if step > decisions.len() {
+        return Err("step out of range");
+    }
+
+    let mut game = Game::replay_of(history, evaluator)?;
+    for record in &decisions[..step] {
+        let action = record.action.to_player_action().unwrap();
+        if game.player_action(record.seat_index as usize, action, record.amount) == 0 {
+            warn!(
+                "replay of hand {} diverged at {:?}",
+                history.hand_number, record
+            );
+            return Err("recorded action rejected by the engine");
+        }
+    }
+
+    let viewer_id = game
+        .players
+        .iter()
+        .find(|(_, seat)| Some(**seat) == viewer_seat)
+        .map(|(player_id, _)| *player_id)
+        .unwrap_or(Uuid::nil());
+    let reproduced = match step == decisions.len() {
+        true => Some(
+            game.hand_history(history.hand_number)
+                .map(|replayed| replayed.same_outcome(history))
+                .unwrap_or(false),
+        ),
+        false => None,
+    };
+
+    Ok(ReplayStep {
+        step,
+        steps: decisions.len(),
+        action: step.checked_sub(1).map(|idx| *decisions[idx]),
+        state: game.collect_state_data(viewer_id),
+        reproduced,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::deck::DeckSource;
+    use crate::poker::game::parse_cards;
+    use crate::poker::history::{HandAction, TableRecord};
+    use crate::poker::player::PlayerAction;
+
+    // seat 0 deals and goes all in with aces, seat 1 calls with kings and hits a set, seat 2 folds
+    fn all_in_hand() -> HandHistory {
+        let deck = parse_cards("AsAh KsKh 7c2d Kd8c3s 9h 4d").unwrap();
+        let mut game = Game::new_game(TableRecord::new_table(3, 5, 10, 1000))
+            .with_deck_source(DeckSource::stacked(vec![deck]).unwrap());
+        for seat in 0..3 {
+            game.join_game(seat, &format!("player {}", seat), 0)
+                .unwrap();
+        }
+        for seat in 0..3 {
+            game.set_ready(seat, true).unwrap();
+        }
+        game.player_action(0, PlayerAction::AllIn, 0);
+        game.player_action(1, PlayerAction::Call, 0);
+        game.player_action(2, PlayerAction::Fold, 0);
+        game.hand_histories()[0].clone()
+    }
+
+    fn replay(step: usize, viewer_seat: Option<usize>) -> Result<ReplayStep, &'static str> {
+        replay_hand(
+            &all_in_hand(),
+            step,
+            viewer_seat,
+            Arc::new(Evaluator::new()),
+        )
+    }
+
+    #[test]
+    fn step_zero_is_the_deal_with_the_blinds_posted() {
+        let replay = replay(0, Some(0)).unwrap();
+        assert_eq!(replay.steps, 3);
+        assert!(replay.action.is_none());
+        assert_eq!(replay.reproduced, None);
+        assert_eq!(replay.state.active_seat, 0);
+        let bets: Vec<u64> = replay
+            .state
+            .players
+            .iter()
+            .flatten()
+            .map(|player| player.bet_amount)
+            .collect();
+        assert_eq!(bets, vec![0, 5, 10]);
+        assert_eq!(replay.state.pot, 15);
+        // the viewer sees their own cards only
+        let cards = parse_cards("AsAh").unwrap();
+        assert_eq!(
+            replay.state.personal_cards,
+            [Some(cards[0]), Some(cards[1])]
+        );
+    }
+
+    #[test]
+    fn a_step_in_between_shows_the_decision_leading_to_it() {
+        let replay = replay(1, None).unwrap();
+        let action = replay.action.unwrap();
+        assert_eq!(action.seat_index, 0);
+        assert_eq!(action.action, HandAction::AllIn);
+        assert_eq!(replay.state.active_seat, 1);
+        assert_eq!(replay.state.personal_cards, [None, None]);
+        assert_eq!(replay.reproduced, None);
+    }
+
+    #[test]
+    fn the_last_step_ends_the_hand_as_recorded() {
+        let replay = replay(3, None).unwrap();
+        assert_eq!(replay.action.unwrap().action, HandAction::Fold);
+        assert_eq!(replay.reproduced, Some(true));
+        let balances: Vec<u64> = replay
+            .state
+            .players
+            .iter()
+            .flatten()
+            .map(|player| player.balance)
+            .collect();
+        assert_eq!(balances, vec![0, 2010, 990]);
+    }
+
+    #[test]
+    fn steps_past_the_end_of_the_hand_are_refused() {
+        assert_eq!(replay(4, None).err(), Some("step out of range"));
+    }
+
+    #[test]
+    fn hands_without_a_recorded_deck_are_not_replayed() {
+        let mut history = all_in_hand();
+        history.deck = None;
+        let replayed = replay_hand(&history, 0, None, Arc::new(Evaluator::new()));
+        assert_eq!(replayed.err(), Some("hand has no recorded deck"));
+    }
+}
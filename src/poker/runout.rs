@@ -0,0 +1,56 @@
+use serde::Serialize;
+
+#[derive(Clone, Copy, Serialize, Debug)]
+pub struct RunoutChoice {
+    pub seat_index: usize,
+    pub boards: Option<u8>, // None until the player chooses
+}
+
+// Players left in an all-in hand choose how many times to run the rest of the board,
+// the board is run as many times as the most cautious of them agreed to.
+#[derive(Clone, Serialize, Debug)]
+pub struct RunoutVote {
+    pub max_boards: u8,
+    pub choices: Vec<RunoutChoice>,
+}
+
+impl RunoutVote {
+    pub fn new_vote(seats: Vec<usize>, max_boards: u8) -> RunoutVote {
+        RunoutVote {
+            max_boards,
+            choices: seats
+                .into_iter()
+                .map(|seat_index| RunoutChoice {
+                    seat_index,
+                    boards: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn choose(&mut self, seat_index: usize, boards: u8) -> Result<(), &'static str> {
+        if boards < 1 || boards > self.max_boards {
+            return Err("unsupported number of boards");
+        }
+        match self
+            .choices
+            .iter_mut()
+            .find(|choice| choice.seat_index == seat_index)
+        {
+            None => Err("player not involved in the runout"),
+            Some(choice) => {
+                choice.boards = Some(boards);
+                Ok(())
+            }
+        }
+    }
+
+    // number of boards once everybody chose
+    pub fn decided(&self) -> Option<u8> {
+        let mut boards = self.max_boards;
+        for choice in &self.choices {
+            boards = boards.min(choice.boards?);
+        }
+        Some(boards)
+    }
+}
@@ -0,0 +1,536 @@
+use crate::poker::game::GamePhase;
+use crate::poker::history::{HandAction, HandHistory};
+use crate::poker::storage::GameStore;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+// Counts behind the stats of one nickname, every field but net_chips counts hands
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct PlayerStats {
+    pub hands: u32,             // dealt in
+    pub vpip: u32,              // put chips in voluntarily before the flop
+    pub pfr: u32,               // raised before the flop
+    pub three_bet_chances: u32, // faced a single raise before the flop
+    pub three_bets: u32,
+    pub aggressive_actions: u32, // bets and raises after the flop, counted one by one
+    pub calls: u32,              // calls after the flop, counted one by one
+    pub saw_flop: u32,
+    pub showdowns: u32,
+    pub showdowns_won: u32, // won at least a part of a pot at showdown
+    pub net_chips: i64,
+}
+
+// What a HUD shows, shares of hands from 0 to 1
+#[derive(Clone, Serialize, Debug)]
+pub struct StatsReport {
+    pub nickname: String,
+    pub hands: u32,
+    pub vpip: f64,
+    pub pfr: f64,
+    pub three_bet: f64,                 // of the chances to 3-bet
+    pub aggression_factor: Option<f64>, // bets and raises per call after the flop, None without calls
+    pub wtsd: f64,                      // went to showdown, of the flops seen
+    pub wsd: f64,                       // won money at showdown, of the showdowns
+    pub net_chips: i64,
+}
+
+fn ratio(part: u32, whole: u32) -> f64 {
+    match whole {
+        0 => 0.0,
+        _ => part as f64 / whole as f64,
+    }
+}
+
+impl PlayerStats {
+    fn add(&mut self, other: &PlayerStats) {
+        self.hands += other.hands;
+        self.vpip += other.vpip;
+        self.pfr += other.pfr;
+        self.three_bet_chances += other.three_bet_chances;
+        self.three_bets += other.three_bets;
+        self.aggressive_actions += other.aggressive_actions;
+        self.calls += other.calls;
+        self.saw_flop += other.saw_flop;
+        self.showdowns += other.showdowns;
+        self.showdowns_won += other.showdowns_won;
+        self.net_chips += other.net_chips;
+    }
+
+    pub fn report(&self, nickname: &str) -> StatsReport {
+        StatsReport {
+            nickname: nickname.to_string(),
+            hands: self.hands,
+            vpip: ratio(self.vpip, self.hands),
+            pfr: ratio(self.pfr, self.hands),
+            three_bet: ratio(self.three_bets, self.three_bet_chances),
+            aggression_factor: match self.calls {
+                0 => None,
+                calls => Some(self.aggressive_actions as f64 / calls as f64),
+            },
+            wtsd: ratio(self.showdowns, self.saw_flop),
+            wsd: ratio(self.showdowns_won, self.showdowns),
+            net_chips: self.net_chips,
+        }
+    }
+}
+
+// What one hand adds to the stats of every player dealt in, by nickname
+pub fn hand_stats(history: &HandHistory) -> Vec<(String, PlayerStats)> {
+    let mut stats: HashMap<u8, PlayerStats> = history
+        .seats
+        .iter()
+        .filter(|seat| seat.cards.is_some())
+        .map(|seat| {
+            let hand = PlayerStats {
+                hands: 1,
+                ..PlayerStats::default()
+            };
+            (seat.seat_index, hand)
+        })
+        .collect();
+
+    let mut street = GamePhase::PreFlop;
+    let mut street_bets: HashMap<u8, u64> = HashMap::new();
+    let mut max_bet = 0;
+    let mut raises = 0; // before the flop
+    let mut opener = None; // first to raise before the flop
+    let mut folded_preflop = Vec::new();
+    for action in &history.actions {
+        if action.street != street {
+            street = action.street;
+            street_bets.clear();
+            max_bet = 0;
+        }
+        let player = match stats.get_mut(&action.seat_index) {
+            Some(player) => player,
+            None => continue,
+        };
+        let bet = street_bets.entry(action.seat_index).or_insert(0);
+        match action.action {
+            HandAction::RunIt => continue,
+            HandAction::UncalledBetReturned => {
+                *bet = bet.saturating_sub(action.amount);
+                player.net_chips += action.amount as i64;
+                continue;
+            }
+            _ => {
+                *bet += action.amount;
+                player.net_chips -= action.amount as i64;
+            }
+        }
+        let raised = matches!(action.action, HandAction::Bet | HandAction::AllIn) && *bet > max_bet;
+        let preflop = street == GamePhase::PreFlop;
+        let voluntary = matches!(
+            action.action,
+            HandAction::Call | HandAction::Bet | HandAction::AllIn
+        );
+        // facing the opening raise, whatever the answer
+        if preflop && raises == 1 && opener != Some(action.seat_index) {
+            if voluntary || action.action == HandAction::Fold {
+                player.three_bet_chances = 1;
+            }
+            if raised {
+                player.three_bets = 1;
+            }
+        }
+        match action.action {
+            HandAction::Fold if preflop => folded_preflop.push(action.seat_index),
+            _ if preflop && voluntary => {
+                player.vpip = 1;
+                if raised {
+                    player.pfr = 1;
+                }
+            }
+            _ if preflop => (),
+            _ if raised => player.aggressive_actions += 1,
+            HandAction::Call | HandAction::AllIn => player.calls += 1,
+            _ => (),
+        }
+        if raised && preflop {
+            raises += 1;
+            if opener.is_none() {
+                opener = Some(action.seat_index);
+            }
+        }
+        max_bet = max_bet.max(*bet);
+    }
+
+    let flop_dealt = history
+        .boards
+        .first()
+        .is_some_and(|board| board[0].is_some());
+    for (seat_index, player) in stats.iter_mut() {
+        if flop_dealt && !folded_preflop.contains(seat_index) {
+            player.saw_flop = 1;
+        }
+        if history
+            .showdown
+            .iter()
+            .any(|record| record.seat_index == *seat_index)
+        {
+            player.showdowns = 1;
+        }
+    }
+    // split like the game does, odd chips go to the first winners
+    for pot in &history.pots {
+        let share = pot.amount / pot.winners.len().max(1) as u64;
+        let mut odd_chips = pot.amount % pot.winners.len().max(1) as u64;
+        for winner_seat in &pot.winners {
+            let mut winnings = share;
+            if odd_chips > 0 {
+                winnings += 1;
+                odd_chips -= 1;
+            }
+            if let Some(player) = stats.get_mut(&(*winner_seat as u8)) {
+                player.net_chips += winnings as i64;
+                if player.showdowns == 1 {
+                    player.showdowns_won = 1;
+                }
+            }
+        }
+    }
+
+    history
+        .seats
+        .iter()
+        .filter_map(|seat| {
+            stats
+                .get(&seat.seat_index)
+                .map(|player| (seat.nickname.clone(), *player))
+        })
+        .collect()
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct StatsData {
+    players: HashMap<String, PlayerStats>,
+    recorded: HashMap<Uuid, usize>, // hands of every running game already counted
+}
+
+// changes close together are written at once
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+enum StatsWrite {
+    Changed,
+    Flush(mpsc::Sender<()>), // answered once the stats are written
+}
+
+// Stats of every nickname over all the tables, fed by the tables as their hands end and kept in
+// the store so they outlive restarts. An account's nickname is reserved for it, but guests playing
+// under one nickname add to the same stats. Writes go through a thread of their own, never holding
+// the tables or the lock up. Cheap to clone, the clones share the book.
+#[derive(Clone, Default)]
+pub struct StatsBook {
+    data: Arc<RwLock<StatsData>>,
+    writer: Option<mpsc::Sender<StatsWrite>>,
+}
+
+impl StatsBook {
+    pub fn new_book() -> StatsBook {
+        StatsBook::default()
+    }
+
+    pub fn with_store(store: GameStore) -> io::Result<StatsBook> {
+        let data = Arc::new(RwLock::new(
+            store.load_document("stats")?.unwrap_or_default(),
+        ));
+        let (writer, writes) = mpsc::channel();
+        let written = data.clone();
+        thread::Builder::new()
+            .name("stats writer".to_string())
+            .spawn(move || write_stats(store, written, writes))?;
+        Ok(StatsBook {
+            data,
+            writer: Some(writer),
+        })
+    }
+
+    // counts the hands of the game that are not counted yet
+    pub fn record_hands(&self, game_id: Uuid, histories: &[HandHistory]) {
+        let mut data = self.data.write().unwrap();
+        let recorded = data.recorded.get(&game_id).copied().unwrap_or(0);
+        if histories.len() <= recorded {
+            return;
+        }
+        for history in &histories[recorded..] {
+            for (nickname, hand) in hand_stats(history) {
+                data.players.entry(nickname).or_default().add(&hand);
+            }
+        }
+        data.recorded.insert(game_id, histories.len());
+        drop(data);
+        self.changed();
+    }
+
+    // the game is archived, none of its hands is coming back
+    pub fn forget_game(&self, game_id: Uuid) {
+        let forgotten = self.data.write().unwrap().recorded.remove(&game_id);
+        if forgotten.is_some() {
+            self.changed();
+        }
+    }
+
+    pub fn report(&self, nickname: &str) -> Option<StatsReport> {
+        let data = self.data.read().unwrap();
+        data.players
+            .get(nickname)
+            .map(|stats| stats.report(nickname))
+    }
+
+    // whether a hand was counted for `nickname`, whatever its case
+    pub fn knows(&self, nickname: &str) -> bool {
+        let data = self.data.read().unwrap();
+        data.players
+            .keys()
+            .any(|known| known.eq_ignore_ascii_case(nickname))
+    }
+
+    // blocks until every change so far is written, for shutting down
+    pub fn flush(&self) {
+        if let Some(writer) = &self.writer {
+            let (done, written) = mpsc::channel();
+            if writer.send(StatsWrite::Flush(done)).is_ok() {
+                let _ = written.recv();
+            }
+        }
+    }
+
+    fn changed(&self) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(StatsWrite::Changed);
+        }
+    }
+}
+
+// Writes a copy of the stats at most once every SAVE_DELAY. Hands recorded since the last write are
+// counted again from the game logs after a crash.
+fn write_stats(store: GameStore, data: Arc<RwLock<StatsData>>, writes: mpsc::Receiver<StatsWrite>) {
+    while let Ok(write) = writes.recv() {
+        let mut flushes = Vec::new();
+        match write {
+            StatsWrite::Flush(done) => flushes.push(done),
+            // lets the changes right behind this one in
+            StatsWrite::Changed => thread::sleep(SAVE_DELAY),
+        }
+        for write in writes.try_iter() {
+            if let StatsWrite::Flush(done) = write {
+                flushes.push(done);
+            }
+        }
+        let snapshot = data.read().unwrap().clone();
+        if let Err(err) = store.save_document("stats", &snapshot) {
+            error!("could not save player stats: {}", err);
+        }
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::game::parse_cards;
+    use crate::poker::games_manager::PotResult;
+    use crate::poker::history::{ActionRecord, SeatRecord, ShowdownRecord, TableRecord};
+
+    // players 0 to n - 1 are dealt in, seat 0 has the button, actions are (street, seat, action, amount)
+    fn hand(
+        players: u8,
+        actions: &[(GamePhase, u8, HandAction, u64)],
+        board: &str,
+        showdown: &[u8],
+        pots: Vec<PotResult>,
+    ) -> HandHistory {
+        let cards = parse_cards("AsAh").unwrap();
+        let mut boards = [None; 5];
+        for (slot, card) in boards.iter_mut().zip(parse_cards(board).unwrap()) {
+            *slot = Some(card);
+        }
+        HandHistory {
+            hand_number: 1,
+            started_at: 0,
+            table: TableRecord::new_table(players as usize, 5, 10, 1000),
+            dealer_seat: 0,
+            small_blind_seat: 1,
+            big_blind_seat: 2 % players as usize,
+            seats: (0..players)
+                .map(|seat_index| SeatRecord {
+                    seat_index,
+                    nickname: format!("player {}", seat_index),
+                    player_id: Uuid::nil(),
+                    stack: 1000,
+                    cards: Some([cards[0], cards[1]]),
+                })
+                .collect(),
+            actions: actions
+                .iter()
+                .map(|&(street, seat_index, action, amount)| ActionRecord {
+                    street,
+                    seat_index,
+                    action,
+                    amount,
+                    balance: 0,
+                })
+                .collect(),
+            boards: vec![boards],
+            showdown: showdown
+                .iter()
+                .map(|&seat_index| ShowdownRecord {
+                    seat_index,
+                    cards: [cards[0], cards[1]],
+                    hands: Vec::new(),
+                })
+                .collect(),
+            pots,
+            deck: None,
+        }
+    }
+
+    fn stats_of(stats: &[(String, PlayerStats)], seat: usize) -> PlayerStats {
+        stats[seat].1
+    }
+
+    #[test]
+    fn an_open_raise_and_a_three_bet() {
+        use GamePhase::PreFlop;
+        use HandAction::*;
+        let history = hand(
+            4,
+            &[
+                (PreFlop, 1, SmallBlind, 5),
+                (PreFlop, 2, BigBlind, 10),
+                (PreFlop, 3, Bet, 30),
+                (PreFlop, 0, Bet, 90),
+                (PreFlop, 1, Fold, 0),
+                (PreFlop, 2, Fold, 0),
+                (PreFlop, 3, Fold, 0),
+                (PreFlop, 0, UncalledBetReturned, 60),
+            ],
+            "",
+            &[],
+            vec![PotResult {
+                amount: 75,
+                board: 0,
+                winners: vec![0],
+                eligible_seats: vec![0],
+            }],
+        );
+        let stats = hand_stats(&history);
+        assert_eq!(stats.len(), 4);
+        assert_eq!(stats[0].0, "player 0");
+
+        // the 3-bet, facing the open raise
+        let three_better = stats_of(&stats, 0);
+        assert_eq!(
+            (
+                three_better.vpip,
+                three_better.pfr,
+                three_better.three_bet_chances,
+                three_better.three_bets
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(three_better.net_chips, 45);
+        assert_eq!(three_better.saw_flop, 0);
+        // the opener has no chance to 3-bet its own raise
+        let opener = stats_of(&stats, 3);
+        assert_eq!(
+            (
+                opener.vpip,
+                opener.pfr,
+                opener.three_bet_chances,
+                opener.three_bets
+            ),
+            (1, 1, 0, 0)
+        );
+        assert_eq!(opener.net_chips, -30);
+        // the blinds fold to a 3-bet, neither voluntary nor a chance to 3-bet
+        for (seat, blind) in [(1, -5), (2, -10)] {
+            let player = stats_of(&stats, seat);
+            assert_eq!(
+                (
+                    player.hands,
+                    player.vpip,
+                    player.pfr,
+                    player.three_bet_chances
+                ),
+                (1, 0, 0, 0)
+            );
+            assert_eq!(player.net_chips, blind);
+        }
+    }
+
+    #[test]
+    fn a_limp_to_a_split_pot() {
+        use GamePhase::*;
+        use HandAction::*;
+        let history = hand(
+            3,
+            &[
+                (PreFlop, 1, SmallBlind, 5),
+                (PreFlop, 2, BigBlind, 10),
+                (PreFlop, 0, Call, 10),
+                (PreFlop, 1, Fold, 0),
+                (PreFlop, 2, Check, 0),
+                (Flop, 2, Bet, 10),
+                (Flop, 0, Call, 10),
+                (Turn, 2, Check, 0),
+                (Turn, 0, Check, 0),
+                (River, 2, Check, 0),
+                (River, 0, Check, 0),
+            ],
+            "2c 7d 9h Js 3c",
+            &[2, 0],
+            // 45 chips, the odd one goes to the first winner
+            vec![PotResult {
+                amount: 45,
+                board: 0,
+                winners: vec![2, 0],
+                eligible_seats: vec![0, 2],
+            }],
+        );
+        let stats = hand_stats(&history);
+
+        let limper = stats_of(&stats, 0);
+        assert_eq!(
+            (limper.vpip, limper.pfr, limper.three_bet_chances),
+            (1, 0, 0)
+        );
+        assert_eq!((limper.aggressive_actions, limper.calls), (0, 1));
+        assert_eq!(
+            (limper.saw_flop, limper.showdowns, limper.showdowns_won),
+            (1, 1, 1)
+        );
+        assert_eq!(limper.net_chips, 2);
+
+        // checking the big blind is not voluntary
+        let big_blind = stats_of(&stats, 2);
+        assert_eq!((big_blind.vpip, big_blind.pfr), (0, 0));
+        assert_eq!((big_blind.aggressive_actions, big_blind.calls), (1, 0));
+        assert_eq!(
+            (
+                big_blind.saw_flop,
+                big_blind.showdowns,
+                big_blind.showdowns_won
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(big_blind.net_chips, 3);
+
+        let small_blind = stats_of(&stats, 1);
+        assert_eq!((small_blind.saw_flop, small_blind.showdowns), (0, 0));
+        assert_eq!(small_blind.net_chips, -5);
+
+        let report = big_blind.report("player 2");
+        assert_eq!(report.aggression_factor, None);
+        assert_eq!((report.wtsd, report.wsd), (1.0, 1.0));
+        assert_eq!(limper.report("player 0").aggression_factor, Some(0.0));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::poker::events::GameEvent;
+use crate::poker::reaper::GameArchive;
+use log::{error, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+// Event logs of the games on disk, one JSON line per event in <dir>/<game_id>.jsonl.
+// Logs are only ever appended to, a last line cut short by a crash is dropped when loading. A log
+// with a broken line before its end is moved to <dir>/quarantine untouched and left out.
+// Reaped games go to <dir>/archive, their summary as <game_id>.json next to their log.
+// Player stats and accounts are rewritten as a whole to <dir>/stats.json and <dir>/accounts.json.
+#[derive(Clone)]
+pub struct GameStore {
+    dir: PathBuf,
+}
+
+impl GameStore {
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<GameStore> {
+        fs::create_dir_all(&dir)?;
+        Ok(GameStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn log_path(&self, game_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", game_id))
+    }
+
+    pub fn append_events(&self, game_id: Uuid, events: &[GameEvent]) -> io::Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event)?);
+            lines.push('\n');
+        }
+        let mut file = private_file()
+            .create(true)
+            .append(true)
+            .open(self.log_path(game_id))?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()
+    }
+
+    // writes the summary first, the log is only moved away once it is safely archived
+    pub fn archive_game(&self, archive: &GameArchive) -> io::Result<()> {
+        let archive_dir = self.dir.join("archive");
+        fs::create_dir_all(&archive_dir)?;
+        let mut file = private_file()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(archive_dir.join(format!("{}.json", archive.game_id)))?;
+        file.write_all(serde_json::to_string_pretty(archive)?.as_bytes())?;
+        file.sync_all()?;
+        let log_path = self.log_path(archive.game_id);
+        if log_path.exists() {
+            fs::rename(
+                log_path,
+                archive_dir.join(format!("{}.jsonl", archive.game_id)),
+            )?;
+        }
+        Ok(())
+    }
+
+    // <dir>/<name>.json, written next to the old file and moved over it, so a crash leaves one or
+    // the other
+    pub fn save_document(&self, name: &str, document: &impl Serialize) -> io::Result<()> {
+        let path = self.dir.join(format!("{}.json", name));
+        let new_path = self.dir.join(format!("{}.json.new", name));
+        let mut file = private_file()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&new_path)?;
+        file.write_all(serde_json::to_string(document)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(new_path, path)
+    }
+
+    pub fn load_document<T: DeserializeOwned>(&self, name: &str) -> io::Result<Option<T>> {
+        let path = self.dir.join(format!("{}.json", name));
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn load_games(&self) -> io::Result<Vec<(Uuid, Vec<GameEvent>)>> {
+        let mut games = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() != Some("jsonl".as_ref()) {
+                continue;
+            }
+            let game_id = match path
+                .file_stem()
+                .and_then(|stem| Uuid::parse_str(&stem.to_string_lossy()).ok())
+            {
+                Some(game_id) => game_id,
+                None => continue,
+            };
+            match load_log(&path) {
+                Ok(events) => games.push((game_id, events)),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    error!("game {} not restored: {}", game_id, err);
+                    self.quarantine(game_id)?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(games)
+    }
+
+    // out of the way of the next start, kept as it was for repairing
+    fn quarantine(&self, game_id: Uuid) -> io::Result<()> {
+        let quarantine_dir = self.dir.join("quarantine");
+        fs::create_dir_all(&quarantine_dir)?;
+        fs::rename(
+            self.log_path(game_id),
+            quarantine_dir.join(format!("{}.jsonl", game_id)),
+        )
+    }
+}
+
+// Event logs hold server seeds not revealed yet and every deck, the accounts their password
+// hashes, so only the server's user reads what is created here, like the session key.
+fn private_file() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+}
+
+fn load_log(path: &Path) -> io::Result<Vec<GameEvent>> {
+    let content = fs::read_to_string(path)?;
+    let mut events = Vec::new();
+    let mut valid_length = 0;
+    for (idx, line) in content.split_inclusive('\n').enumerate() {
+        if !line.ends_with('\n') {
+            // the last line, its write never finished
+            break;
+        }
+        match serde_json::from_str(line) {
+            Ok(event) => events.push(event),
+            Err(err) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} of {}: {}", idx + 1, path.display(), err),
+                ))
+            }
+        }
+        valid_length += line.len();
+    }
+    if valid_length < content.len() {
+        // cut the broken tail off, or the next append would be glued to it
+        warn!(
+            "dropping {} broken bytes at the end of {}",
+            content.len() - valid_length,
+            path.display()
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(valid_length as u64)?;
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn store_with_log(content: &str) -> (GameStore, Uuid) {
+        let store =
+            GameStore::open(env::temp_dir().join(format!("rusty-poker-{}", Uuid::new_v4())))
+                .unwrap();
+        let game_id = Uuid::new_v4();
+        fs::write(store.log_path(game_id), content).unwrap();
+        (store, game_id)
+    }
+
+    fn event_line() -> String {
+        serde_json::to_string(&GameEvent::GameStarted).unwrap() + "\n"
+    }
+
+    #[test]
+    fn a_torn_last_line_is_cut_off() {
+        let (store, game_id) = store_with_log(&(event_line() + &event_line() + "{\"Game"));
+        let games = store.load_games().unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!((games[0].0, games[0].1.len()), (game_id, 2));
+        let content = fs::read_to_string(store.log_path(game_id)).unwrap();
+        assert_eq!(content, event_line() + &event_line());
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn a_broken_line_before_the_end_quarantines_the_log() {
+        let content = event_line() + "{\"Game\n" + &event_line();
+        let (store, game_id) = store_with_log(&content);
+        assert!(store.load_games().unwrap().is_empty());
+        assert!(!store.log_path(game_id).exists());
+        let quarantined = store
+            .dir()
+            .join("quarantine")
+            .join(format!("{}.jsonl", game_id));
+        assert_eq!(fs::read_to_string(quarantined).unwrap(), content);
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn logs_and_documents_are_only_readable_by_the_server() {
+        use std::os::unix::fs::PermissionsExt;
+        let store =
+            GameStore::open(env::temp_dir().join(format!("rusty-poker-{}", Uuid::new_v4())))
+                .unwrap();
+        let game_id = Uuid::new_v4();
+        store
+            .append_events(game_id, &[GameEvent::GameStarted])
+            .unwrap();
+        store.save_document("accounts", &vec!["alice"]).unwrap();
+        for path in [store.log_path(game_id), store.dir().join("accounts.json")] {
+            let mode = fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+}
@@ -0,0 +1,393 @@
+use crate::poker::bot::{bot_to_act, play_bot, Bot, BotKind};
+use crate::poker::buy_in::BuyInKind;
+use crate::poker::game::Game;
+use crate::poker::games_manager::game_data;
+use crate::poker::player::PlayerAction;
+use crate::poker::reaper::{unix_now, GameArchive, ReapReason, ReaperConfig};
+use crate::poker::stats::StatsBook;
+use crate::poker::storage::GameStore;
+use log::{error, info};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{sleep_until, Instant};
+use uuid::Uuid;
+
+pub type Reply<T> = oneshot::Sender<T>;
+
+// pause before a bot acts, so players can follow what happens
+const BOT_DELAY: Duration = Duration::from_secs(1);
+
+// Everything a table can be asked to do, each command answers through its reply channel
+pub enum TableCommand {
+    Join {
+        seat_index: u8,
+        nickname: String,
+        appearance_type: u8,
+        reply: Reply<Result<Uuid, &'static str>>,
+    },
+    AddBot {
+        seat_index: Option<u8>, // first free seat when None
+        kind: BotKind,
+        reply: Reply<Result<u8, &'static str>>,
+    },
+    SetReady {
+        player_id: Uuid,
+        ready: bool,
+        reply: Reply<Result<bool, &'static str>>,
+    },
+    Act {
+        player_id: Uuid,
+        action: PlayerAction,
+        amount: u64,
+        reply: Reply<Result<u8, &'static str>>,
+    },
+    BuyChips {
+        player_id: Uuid,
+        kind: BuyInKind,
+        amount: u64,
+        reply: Reply<Result<u64, &'static str>>,
+    },
+    SetClientSeed {
+        player_id: Uuid,
+        seed: String,
+        reply: Reply<Result<(), &'static str>>,
+    },
+    Quit {
+        player_id: Uuid,
+        reply: Reply<Result<u64, &'static str>>,
+    },
+    Pause {
+        reply: Reply<()>,
+    },
+    // archives the game and stops the table when it expired
+    Reap {
+        config: ReaperConfig,
+        reply: Reply<Option<ReapReason>>,
+    },
+    // read only access, the closure sends its own answer
+    Inspect(Box<dyn FnOnce(&Game) + Send>),
+}
+
+// Way in to a running table, cheap to clone
+#[derive(Clone)]
+pub struct TableHandle {
+    commands: mpsc::Sender<TableCommand>,
+    updates: watch::Receiver<usize>, // number of events in the game, bumped by every change
+}
+
+impl TableHandle {
+    pub async fn request<T>(
+        &self,
+        command: impl FnOnce(Reply<T>) -> TableCommand,
+    ) -> Result<T, &'static str> {
+        let (reply, answer) = oneshot::channel();
+        // a stopped table dropped its receiver, the game is gone
+        self.commands
+            .send(command(reply))
+            .await
+            .map_err(|_| "game not found")?;
+        answer.await.map_err(|_| "game not found")
+    }
+
+    pub async fn inspect<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Game) -> T + Send + 'static,
+    ) -> Result<T, &'static str> {
+        self.request(|reply| {
+            TableCommand::Inspect(Box::new(move |game| {
+                let _ = reply.send(f(game));
+            }))
+        })
+        .await
+    }
+
+    pub fn updates(&self) -> watch::Receiver<usize> {
+        self.updates.clone()
+    }
+}
+
+// A table runs as a task owning its game. Commands are handled one at a time, so a busy
+// table only ever delays its own players. The action clock, the pause between hands, the blind
+// levels and the bots live in the same loop.
+struct Table {
+    game_id: Uuid,
+    game: Game,
+    bots: HashMap<usize, Box<dyn Bot>>, // by seats
+    store: Option<GameStore>,
+    saved_events: usize, // events already in the store
+    stats: StatsBook,
+    recorded_hands: usize, // hand histories already given to the stats
+    last_activity: u64,    // unix time of the last new event
+    updates: watch::Sender<usize>,
+}
+
+// saves what the game has not stored yet before the table takes commands
+pub fn spawn_table(
+    game_id: Uuid,
+    game: Game,
+    store: Option<GameStore>,
+    saved_events: usize,
+    stats: StatsBook,
+) -> TableHandle {
+    let (commands, receiver) = mpsc::channel(64);
+    let (updates, listener) = watch::channel(game.events().len());
+    let bots = game
+        .bot_seats()
+        .into_iter()
+        .map(|(seat, kind)| (seat, kind.new_bot(rand::random(), game.evaluator())))
+        .collect();
+    let mut table = Table {
+        game_id,
+        game,
+        bots,
+        store,
+        saved_events,
+        stats,
+        recorded_hands: 0,
+        last_activity: unix_now(),
+        updates,
+    };
+    table.save();
+    // hands a crash kept from the stats are counted now, the book skips the others
+    table.record_stats();
+    tokio::spawn(table.run(receiver));
+    TableHandle {
+        commands,
+        updates: listener,
+    }
+}
+
+impl Table {
+    async fn run(mut self, mut commands: mpsc::Receiver<TableCommand>) {
+        let mut deadline = self.clock();
+        loop {
+            let events = self.game.events().len();
+            let mut timed_out = false;
+            let mut level_ended = false;
+            let level_deadline = self.level_clock();
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => {
+                        if !self.handle(command) {
+                            break;
+                        }
+                    }
+                    None => break, // every handle was dropped
+                },
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    timed_out = true;
+                    match bot_to_act(&self.game, &self.bots) {
+                        Some(seat) => {
+                            let bot = self.bots.get_mut(&seat).unwrap();
+                            play_bot(&mut self.game, seat, bot.as_mut());
+                        }
+                        None if self.game.next_hand_at().is_some() => {
+                            self.game.deal_next_hand();
+                        }
+                        None => {
+                            self.game.time_out();
+                        }
+                    }
+                }
+                _ = sleep_until(level_deadline.unwrap_or_else(Instant::now)), if level_deadline.is_some() => {
+                    level_ended = true;
+                    self.game.raise_blinds();
+                }
+            }
+            self.save();
+            if level_ended {
+                // the player to act keeps the time left
+            } else if self.game.events().len() != events {
+                deadline = self.clock();
+            } else if timed_out {
+                // nobody could act, wait for the next change
+                deadline = None;
+            }
+        }
+        info!("table {} stopped", self.game_id);
+    }
+
+    // returns false once the table is done
+    fn handle(&mut self, command: TableCommand) -> bool {
+        match command {
+            TableCommand::Join {
+                seat_index,
+                nickname,
+                appearance_type,
+                reply,
+            } => {
+                let _ = reply.send(self.game.join_game(seat_index, &nickname, appearance_type));
+            }
+            TableCommand::AddBot {
+                seat_index,
+                kind,
+                reply,
+            } => {
+                let result = self.game.add_bot(seat_index, kind);
+                if let Ok(seat) = result {
+                    self.bots.insert(
+                        seat as usize,
+                        kind.new_bot(rand::random(), self.game.evaluator()),
+                    );
+                }
+                let _ = reply.send(result);
+            }
+            TableCommand::SetReady {
+                player_id,
+                ready,
+                reply,
+            } => {
+                let result = self
+                    .seat_of(player_id)
+                    .and_then(|seat| self.game.set_ready(seat, ready));
+                let _ = reply.send(result);
+            }
+            TableCommand::Act {
+                player_id,
+                action,
+                amount,
+                reply,
+            } => {
+                let result = self
+                    .seat_of(player_id)
+                    .map(|seat| self.game.player_action(seat, action, amount));
+                let _ = reply.send(result);
+            }
+            TableCommand::BuyChips {
+                player_id,
+                kind,
+                amount,
+                reply,
+            } => {
+                let result = self
+                    .seat_of(player_id)
+                    .and_then(|seat| self.game.buy_chips(seat, kind, amount));
+                let _ = reply.send(result);
+            }
+            TableCommand::SetClientSeed {
+                player_id,
+                seed,
+                reply,
+            } => {
+                let result = self
+                    .seat_of(player_id)
+                    .and_then(|seat| self.game.set_client_seed(seat, &seed));
+                let _ = reply.send(result);
+            }
+            TableCommand::Quit { player_id, reply } => {
+                let _ = reply.send(self.game.cash_out(player_id));
+            }
+            TableCommand::Pause { reply } => {
+                self.game.pause();
+                let _ = reply.send(());
+            }
+            TableCommand::Reap { config, reply } => {
+                let reaped = self.reap(&config);
+                let _ = reply.send(reaped);
+                if reaped.is_some() {
+                    return false;
+                }
+            }
+            TableCommand::Inspect(f) => f(&self.game),
+        }
+        true
+    }
+
+    fn seat_of(&self, player_id: Uuid) -> Result<usize, &'static str> {
+        match self.game.players.get(&player_id) {
+            None => Err("player not in this game"),
+            Some(seat) => Ok(*seat),
+        }
+    }
+
+    // when a bot acts next, or when the player to act has to have decided
+    fn clock(&self) -> Option<Instant> {
+        if bot_to_act(&self.game, &self.bots).is_some() {
+            return Some(Instant::now() + BOT_DELAY);
+        }
+        if let (Some(next_hand_at), false) = (self.game.next_hand_at(), self.game.paused()) {
+            return Some(instant_at(next_hand_at));
+        }
+        match self.game.action_clock > 0 && self.game.hand_in_progress() {
+            true => Some(Instant::now() + Duration::from_secs(self.game.action_clock)),
+            false => None,
+        }
+    }
+
+    // when the blinds go up, they stay while the game is paused
+    fn level_clock(&self) -> Option<Instant> {
+        match self.game.paused() {
+            true => None,
+            false => self.game.next_level_at().map(instant_at),
+        }
+    }
+
+    // appends the events of the game that are not stored yet and tells listeners about them
+    fn save(&mut self) {
+        let events = self.game.events();
+        if events.len() == self.saved_events {
+            return;
+        }
+        if let Some(store) = &self.store {
+            if let Err(err) = store.append_events(self.game_id, &events[self.saved_events..]) {
+                error!("could not save game {}: {}", self.game_id, err);
+                return;
+            }
+        }
+        self.saved_events = events.len();
+        self.last_activity = unix_now();
+        let _ = self.updates.send(self.saved_events);
+        self.record_stats();
+    }
+
+    // only once the hands are saved, a hand lost with its events must not count
+    fn record_stats(&mut self) {
+        let hands = self.game.hand_histories();
+        if hands.len() > self.recorded_hands {
+            self.stats.record_hands(self.game_id, hands);
+            self.recorded_hands = hands.len();
+        }
+    }
+
+    // Archives the game when it had no new event for longer than its ttl allows
+    fn reap(&mut self, config: &ReaperConfig) -> Option<ReapReason> {
+        let now = unix_now();
+        let reason = config.reason(self.game.game_state, now.saturating_sub(self.last_activity))?;
+        let game = &self.game;
+        let archive = GameArchive {
+            game_id: self.game_id,
+            archived_at: now,
+            reason,
+            game: game_data(self.game_id, game),
+            players: game
+                .collect_state_data(Uuid::nil())
+                .players
+                .into_iter()
+                .flatten()
+                .collect(),
+            hands: game.hand_histories().to_vec(),
+            ledger: game.ledger().entries().to_vec(),
+        };
+        if let Some(store) = &self.store {
+            if let Err(err) = store.archive_game(&archive) {
+                // keep the game rather than lose its results, the next sweep tries again
+                error!("could not archive game {}: {}", self.game_id, err);
+                return None;
+            }
+        }
+        self.stats.forget_game(self.game_id);
+        info!(
+            "reaped game {} ({:?}, {} hands played)",
+            self.game_id,
+            reason,
+            archive.hands.len()
+        );
+        Some(reason)
+    }
+}
+
+// a unix time in seconds on the runtime's clock, right away once it is past
+fn instant_at(unix_time: u64) -> Instant {
+    Instant::now() + Duration::from_secs(unix_time.saturating_sub(unix_now()))
+}